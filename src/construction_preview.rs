@@ -1,59 +1,156 @@
 use bevy::prelude::*;
+use bevy_ecs_tilemap::map::{TilemapGridSize, TilemapType};
 use bevy_mod_picking::pointer::PointerId;
 
 use crate::{
-    ui::CurrentIntention, units::BuildingTypeMap, CameraPointerParam, MapQuery, MapQueryHelpers,
+    level::{CurrentLevel, TilePath},
+    placement::{PlacementDrag, PlacementParam},
+    ui::CurrentIntention,
+    units::BuildingTypeMap,
+    CameraPointerParam, Funds, MapQuery, MapQueryHelpers,
 };
 
 #[derive(Component)]
 pub struct BuildingPreview;
 
+/// Number of walking path tiles the previewed building would reach, if it
+/// has a range.
+#[derive(Resource, Default)]
+pub struct PreviewCoverage(pub Option<usize>);
+
 pub fn sys_hover_building_effect(
     mut commands: Commands,
     pointers: CameraPointerParam,
     current_inspector: Res<CurrentIntention>,
     map_query: MapQuery,
-    mut building_preview_query: Query<(Entity, &mut Transform), With<BuildingPreview>>,
+    mut building_preview_query: Query<
+        (Entity, &mut Transform, &mut Sprite, &mut Handle<Image>),
+        With<BuildingPreview>,
+    >,
     building_types: Res<BuildingTypeMap>,
+    placement: PlacementParam,
+    drag: Res<PlacementDrag>,
+    path_query: Query<
+        (&TilePath, &TilemapGridSize, &TilemapType, &GlobalTransform),
+        With<CurrentLevel>,
+    >,
+    mut coverage: ResMut<PreviewCoverage>,
+    funds: Res<Funds>,
+    mut gizmos: Gizmos,
 ) {
     let CurrentIntention::Prospective(typ) = *current_inspector else {
-        if let Ok((entity, _)) = building_preview_query.get_single() {
+        for (entity, _, _, _) in building_preview_query.iter() {
             commands.entity(entity).despawn();
-        };
+        }
+        coverage.0 = None;
         return;
     };
 
     let Some(pos) = pointers.get_world_pointer_location(PointerId::Mouse) else {
         return;
     };
-    let Some(mut snapped_pos) = map_query.snap_to_tile_center(&pos) else {
-        return;
-    };
-    snapped_pos += map_query.tile_center_to_corner();
-
     let Some(building_type) = building_types.type_map.get(&typ) else {
         warn!("Sprite data was not found for prospective entity type");
         return;
     };
-    match building_preview_query.get_single_mut().ok() {
-        Some((_, mut transform)) => {
-            *transform = Transform::from_translation(snapped_pos);
+    let tile_size = building_type.tile_size();
+    let Ok((_, _, grid_size, map_transform)) = map_query.get_single() else {
+        return;
+    };
+    let grid = Vec2::new(grid_size.x, grid_size.y);
+    let sprite_size = Some(tile_size.as_vec2() * grid);
+
+    // Every building in the current drag, or just the hovered one.
+    let planned = placement.plan_drag(drag.0.unwrap_or(pos), pos, building_type.as_ref());
+    let mut previews = building_preview_query.iter_mut();
+    let mut spent = 0;
+    for (site_pos, site) in planned.iter() {
+        let Some((snapped_pos, _)) = map_query.snap_footprint(site_pos, tile_size) else {
+            continue;
+        };
+        let valid = site.is_ok() && spent + building_type.cost() <= funds.0;
+        if valid {
+            spent += building_type.cost();
+        }
+        let preview_color = if valid {
+            Color::linear_rgba(0.2, 0.3, 1.0, 0.4)
+        } else {
+            Color::linear_rgba(1.0, 0.1, 0.1, 0.4)
+        };
+        let footprint_color = if valid {
+            bevy::color::palettes::css::LIME
+        } else {
+            bevy::color::palettes::css::RED
+        };
+        if let Ok(site) = placement.site_at(site_pos, tile_size) {
+            for tile_pos in site.footprint.tiles.iter() {
+                let tile_center = map_transform.translation().xy()
+                    + Vec2::new(tile_pos.x as f32, tile_pos.y as f32) * grid;
+                gizmos.rect_2d(tile_center, 0.0, grid, footprint_color);
+            }
         }
-        None => {
-            commands.spawn((
-                BuildingPreview,
-                SpriteBundle {
-                    texture: building_type.sprite_image().clone(),
-                    transform: Transform::from_translation(snapped_pos),
-                    sprite: Sprite {
-                        color: Color::linear_rgba(0.2, 0.3, 1.0, 0.4),
+        match previews.next() {
+            Some((_, mut transform, mut sprite, mut texture)) => {
+                *transform = Transform::from_translation(snapped_pos);
+                // The prospective building may have changed since last frame.
+                if *texture != *building_type.sprite_image() {
+                    *texture = building_type.sprite_image().clone();
+                }
+                sprite.color = preview_color;
+                sprite.custom_size = sprite_size;
+            }
+            None => {
+                commands.spawn((
+                    BuildingPreview,
+                    SpriteBundle {
+                        texture: building_type.sprite_image().clone(),
+                        transform: Transform::from_translation(snapped_pos),
+                        sprite: Sprite {
+                            color: preview_color,
+                            custom_size: sprite_size,
+                            ..Default::default()
+                        },
                         ..Default::default()
                     },
-                    ..Default::default()
-                },
-            ));
+                ));
+            }
         }
     }
+    for (entity, _, _, _) in previews {
+        commands.entity(entity).despawn();
+    }
+
+    let Some(range) = building_type.range() else {
+        coverage.0 = None;
+        return;
+    };
+    let Some((center, _)) = map_query.snap_footprint(&pos, tile_size) else {
+        return;
+    };
+    let center = center.xy();
+    gizmos.circle_2d(center, range as f32, bevy::color::palettes::css::BLUE);
+
+    let Ok((TilePath { path }, grid_size, map_type, map_transform)) = path_query.get_single()
+    else {
+        coverage.0 = None;
+        return;
+    };
+    let mut covered = 0;
+    for tile_pos in path.iter() {
+        let tile_center =
+            map_transform.translation().xy() + tile_pos.center_in_world(grid_size, map_type);
+        if tile_center.distance(center) > range as f32 {
+            continue;
+        }
+        covered += 1;
+        gizmos.rect_2d(
+            tile_center,
+            0.0,
+            Vec2::new(grid_size.x, grid_size.y),
+            bevy::color::palettes::css::LIME,
+        );
+    }
+    coverage.0 = Some(covered);
 }
 
 pub struct BuildingPreviewPlugin;
@@ -64,7 +161,7 @@ pub fn building_preview_active(res: Res<CurrentIntention>) -> bool {
 
 impl Plugin for BuildingPreviewPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
+        app.init_resource::<PreviewCoverage>().add_systems(
             Update,
             sys_hover_building_effect
                 .run_if(building_preview_active.or_else(resource_changed::<CurrentIntention>)),
@@ -1,6 +1,24 @@
 use bevy::{math::vec2, prelude::*};
+use bevy_ecs_tilemap::tiles::TileStorage;
+use bevy_rapier2d::prelude::{
+    ActiveEvents, Collider, Damping, GravityScale, Restitution, RigidBody, Velocity,
+};
+use rand::Rng;
 
-use crate::{fruit_type::FruitSpeciesMap, SpatialTracked};
+use crate::{
+    fruit_type::FruitSpeciesMap,
+    level::CurrentLevel,
+    nutrients::TileWater,
+    tree::{Pollen, SeedBank, Tree, TreeGenome},
+    units::{
+        harvester::{HarvestLoad, HarvestStats},
+        PathFollower,
+    },
+    MapQuery, MapQueryHelpers, SpatialTracked,
+};
+
+/// Chance that a harvested fruit also yields a seed.
+pub const SEED_DROP_CHANCE: f64 = 0.2;
 
 #[derive(Component)]
 pub struct FruitBranch {
@@ -9,7 +27,6 @@ pub struct FruitBranch {
 
 #[derive(Component)]
 pub enum FruitBranchAttachment {
-    #[allow(dead_code)]
     Fruit(Entity),
 }
 
@@ -36,13 +53,13 @@ pub fn sys_fruit_branch_spawn_fruit(
 
 #[derive(Component)]
 pub struct Fruit {
-    fruit_type: usize,
+    pub fruit_type: usize,
 }
 
 #[derive(Component)]
 pub enum FruitGrowthState {
     Bud { seconds_of_growth: f32 },
-    Fruited,
+    Fruited { seconds_ripe: f32 },
 }
 
 impl Fruit {
@@ -64,50 +81,285 @@ impl Fruit {
 pub fn sys_fruit_grow(
     time: Res<Time>,
     mut commands: Commands,
-    mut fruits: Query<(Entity, &Fruit, &mut FruitGrowthState)>,
+    mut fruits: Query<(
+        Entity,
+        &Fruit,
+        &mut FruitGrowthState,
+        &Parent,
+        &GlobalTransform,
+    )>,
+    branches: Query<&Parent, With<FruitBranch>>,
+    trees: Query<(&Tree, Has<PathFollower>)>,
+    map_query: MapQuery,
+    tile_storage: Query<&TileStorage, With<CurrentLevel>>,
+    tile_water: Query<&TileWater>,
     fruit_map: Res<FruitSpeciesMap>,
 ) {
-    for (fruit_ent, fruit, mut growth) in fruits.iter_mut() {
+    for (fruit_ent, fruit, mut growth, branch_ent, fruit_gt) in fruits.iter_mut() {
         let fruit_type = fruit_map
             .species_vector
             .get(fruit.fruit_type)
             .unwrap_or_else(|| panic!("Unknown fruit type {}", fruit.fruit_type));
+        let tree = branches
+            .get(branch_ent.get())
+            .and_then(|tree_ent| trees.get(tree_ent.get()));
         match *growth {
             FruitGrowthState::Bud {
                 ref mut seconds_of_growth,
             } => {
-                *seconds_of_growth += time.delta_seconds();
+                // Trees off the map (waiting for the next wave) are not
+                // affected by drought.
+                let water_saturation = map_query
+                    .tile_pos_at(&fruit_gt.translation().xy())
+                    .and_then(|tile_pos| tile_storage.get_single().ok()?.get(&tile_pos))
+                    .and_then(|tile_ent| tile_water.get(tile_ent).ok())
+                    .map_or(1.0, TileWater::saturation);
+                let growth_rate =
+                    tree.map_or(1.0, |(tree, _)| tree.genome.growth_rate(water_saturation));
+                *seconds_of_growth += time.delta_seconds() * growth_rate;
                 if *seconds_of_growth >= fruit_type.growth_time_secs {
-                    commands
-                        .entity(fruit_ent)
-                        .insert((FruitGrowthState::Fruited, fruit_type.fruit_image.clone()));
+                    commands.entity(fruit_ent).insert((
+                        FruitGrowthState::Fruited { seconds_ripe: 0.0 },
+                        fruit_type.fruit_image.clone(),
+                    ));
+                }
+            }
+            FruitGrowthState::Fruited {
+                ref mut seconds_ripe,
+            } => {
+                // Ripe fruit on trees waiting off the map in the next wave
+                // queue or the reserve keeps until the tree walks again.
+                let walking = tree.is_ok_and(|(_, walking)| walking);
+                if !walking {
+                    continue;
+                }
+                *seconds_ripe += time.delta_seconds();
+                if *seconds_ripe >= fruit_type.harvest_window_secs {
+                    commands.trigger_targets(FruitKnockedOffEvent, fruit_ent);
                 }
             }
-            FruitGrowthState::Fruited => (),
         }
     }
 }
 
 #[derive(Event)]
 pub struct HarvestFruitEvent {
-    #[allow(dead_code)]
     pub harvester_ent: Entity,
 }
 
+/// Claim placed on a ripe fruit by the one harvester allowed to pick it.
+/// `HarvestFruitEvent`s from any other harvester are ignored.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub struct HarvestReservation {
+    pub harvester_ent: Entity,
+}
+
+pub const FRUIT_FLIGHT_SECS: f32 = 0.6;
+pub const FRUIT_FLIGHT_ARC_HEIGHT: f32 = 24.0;
+
+/// A harvested fruit travelling from its branch to the harvester that picked
+/// it. Score (and any seed) is credited when it arrives.
+#[derive(Component)]
+pub struct FruitFlight {
+    pub harvester_ent: Entity,
+    pub start: Vec3,
+    pub elapsed_secs: f32,
+    pub seed: Option<TreeGenome>,
+}
+
 pub fn obs_fruit_harvested(
     event: Trigger<HarvestFruitEvent>,
-    mut score: ResMut<super::Score>,
     mut commands: Commands,
-    fruit_query: Query<&Parent, With<Fruit>>,
+    fruit_query: Query<
+        (
+            &Parent,
+            &FruitGrowthState,
+            &GlobalTransform,
+            &HarvestReservation,
+        ),
+        With<Fruit>,
+    >,
+    branch_query: Query<&Parent, With<FruitBranch>>,
+    tree_query: Query<(&Tree, &Pollen)>,
 ) {
     let target_fruit = event.entity();
-    let Ok(parent_branch_ent) = fruit_query.get(event.entity()) else {
+    let harvester_ent = event.event().harvester_ent;
+    let Ok((parent_branch_ent, FruitGrowthState::Fruited { .. }, fruit_gt, reservation)) =
+        fruit_query.get(target_fruit)
+    else {
         return;
     };
+    if reservation.harvester_ent != harvester_ent {
+        return;
+    }
+
+    let mut rng = rand::thread_rng();
+    let seed = if rng.gen_bool(SEED_DROP_CHANCE) {
+        branch_query
+            .get(parent_branch_ent.get())
+            .and_then(|tree_ent| tree_query.get(tree_ent.get()))
+            .ok()
+            .map(|(tree, pollen)| {
+                let pollinator = pollen.0.as_ref().unwrap_or(&tree.genome);
+                tree.genome.cross(pollinator, &mut rng)
+            })
+    } else {
+        None
+    };
 
-    score.0 += 1;
-    commands.entity(target_fruit).despawn();
+    commands
+        .entity(target_fruit)
+        .remove_parent_in_place()
+        .remove::<(FruitGrowthState, HarvestReservation, SpatialTracked)>()
+        .insert(FruitFlight {
+            harvester_ent,
+            start: fruit_gt.translation(),
+            elapsed_secs: 0.0,
+            seed,
+        });
     commands
         .entity(parent_branch_ent.get())
         .remove::<FruitBranchAttachment>();
 }
+
+/// Fired on a fruit that drops from its branch without being harvested,
+/// either because it was shaken loose or because it over-ripened.
+#[derive(Event)]
+pub struct FruitKnockedOffEvent;
+
+/// Seconds a fallen fruit lies on the ground before it rots away.
+pub const FALLEN_FRUIT_ROT_SECS: f32 = 15.0;
+
+/// A fruit lying (or still rolling) on the ground as a physics body. Only
+/// fruit that was ripe when it fell can still be collected by a net.
+#[derive(Component)]
+pub struct FallenFruit {
+    pub ripe: bool,
+    pub seconds_on_ground: f32,
+}
+
+pub fn obs_fruit_knocked_off(
+    event: Trigger<FruitKnockedOffEvent>,
+    mut commands: Commands,
+    fruit_query: Query<(&Parent, &FruitGrowthState), With<Fruit>>,
+) {
+    let target_fruit = event.entity();
+    let Ok((parent_branch_ent, growth)) = fruit_query.get(target_fruit) else {
+        return;
+    };
+    let ripe = matches!(growth, FruitGrowthState::Fruited { .. });
+
+    let mut rng = rand::thread_rng();
+    let direction = Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU));
+    commands
+        .entity(target_fruit)
+        .remove_parent_in_place()
+        .remove::<(FruitGrowthState, HarvestReservation, SpatialTracked)>()
+        .insert((
+            FallenFruit {
+                ripe,
+                seconds_on_ground: 0.0,
+            },
+            RigidBody::Dynamic,
+            Collider::ball(3.0),
+            Restitution::coefficient(0.6),
+            Damping {
+                linear_damping: 3.0,
+                angular_damping: 3.0,
+            },
+            GravityScale(0.0),
+            Velocity::linear(direction * rng.gen_range(60.0..120.0)),
+            ActiveEvents::COLLISION_EVENTS,
+        ));
+    commands
+        .entity(parent_branch_ent.get())
+        .remove::<FruitBranchAttachment>();
+}
+
+pub fn sys_fallen_fruit_rot(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut fallen: Query<(Entity, &mut FallenFruit, &mut Sprite)>,
+) {
+    for (fruit_ent, mut fallen, mut sprite) in fallen.iter_mut() {
+        fallen.seconds_on_ground += time.delta_seconds();
+        if fallen.seconds_on_ground >= FALLEN_FRUIT_ROT_SECS {
+            commands.entity(fruit_ent).despawn_recursive();
+            continue;
+        }
+        let rot = fallen.seconds_on_ground / FALLEN_FRUIT_ROT_SECS;
+        sprite.color = Color::WHITE.mix(&Color::srgb(0.4, 0.3, 0.2), rot);
+    }
+}
+
+/// Hands fallen fruit over to a net, flying it in like a regular harvest.
+pub fn collect_fallen_fruit(commands: &mut Commands, fruit_ent: Entity, start: Vec3, net: Entity) {
+    commands
+        .entity(fruit_ent)
+        .remove::<(
+            FallenFruit,
+            RigidBody,
+            Collider,
+            Restitution,
+            Damping,
+            GravityScale,
+            Velocity,
+            ActiveEvents,
+        )>()
+        .insert(FruitFlight {
+            harvester_ent: net,
+            start,
+            elapsed_secs: 0.0,
+            seed: None,
+        });
+}
+
+pub fn sys_fruit_flight(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut score: ResMut<super::Score>,
+    mut funds: ResMut<super::Funds>,
+    mut seed_bank: ResMut<SeedBank>,
+    mut fruits: Query<(Entity, &Fruit, &mut FruitFlight, &mut Transform)>,
+    harvesters: Query<&GlobalTransform>,
+    mut worker_loads: Query<&mut HarvestLoad>,
+    mut harvest_stats: Query<&mut HarvestStats>,
+    fruit_map: Res<FruitSpeciesMap>,
+) {
+    for (fruit_ent, fruit, mut flight, mut transform) in fruits.iter_mut() {
+        let Ok(harvester_gt) = harvesters.get(flight.harvester_ent) else {
+            // The harvester is gone, so the fruit has nowhere to land.
+            commands.entity(fruit_ent).despawn_recursive();
+            continue;
+        };
+        flight.elapsed_secs += time.delta_seconds();
+        let t = (flight.elapsed_secs / FRUIT_FLIGHT_SECS).min(1.0);
+        if t >= 1.0 {
+            let value = fruit_map
+                .species_vector
+                .get(fruit.fruit_type)
+                .map_or(1, |species| species.value);
+            if let Ok(mut stats) = harvest_stats.get_mut(flight.harvester_ent) {
+                stats.fruit_collected += 1;
+                stats.value_collected += value;
+            }
+            // Workers only score once they bring their load to a depot.
+            if let Ok(mut load) = worker_loads.get_mut(flight.harvester_ent) {
+                load.fruit_count += 1;
+                load.value += value;
+                load.seeds.extend(flight.seed.take());
+            } else {
+                score.0 += value;
+                funds.0 += value;
+                seed_bank.0.extend(flight.seed.take());
+            }
+            commands.entity(fruit_ent).despawn_recursive();
+            continue;
+        }
+        let eased = t * t * (3.0 - 2.0 * t);
+        let target = harvester_gt.translation();
+        transform.translation = flight.start.lerp(target, eased)
+            + Vec3::Y * FRUIT_FLIGHT_ARC_HEIGHT * 4.0 * t * (1.0 - t);
+        transform.translation.z = flight.start.z.max(target.z) + 1.0;
+    }
+}
@@ -4,16 +4,23 @@ use bevy::{
     prelude::{Res, ResMut, Resource},
     render::texture::Image,
 };
+use serde::Deserialize;
 
-#[derive(Debug, PartialEq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Hash, Deserialize)]
 pub enum FruitGenus {
     Carrot,
+    Cherry,
+    Tomato,
 }
 
 pub struct FruitSpecies {
     #[allow(dead_code)]
     pub genus: FruitGenus,
     pub growth_time_secs: f32,
+    /// Score awarded when a fruit of this species is harvested.
+    pub value: usize,
+    /// Seconds a ripe fruit stays on the branch before it drops.
+    pub harvest_window_secs: f32,
     pub fruit_image: Handle<Image>,
 }
 
@@ -38,6 +45,22 @@ pub fn sys_init_fruit_species(
     fruit_map.species_vector.push(FruitSpecies {
         genus: FruitGenus::Carrot,
         growth_time_secs: 6.0,
+        value: 2,
+        harvest_window_secs: 12.0,
         fruit_image: asset_server.load("Crops/Carrot/carrot.png"),
     });
+    fruit_map.species_vector.push(FruitSpecies {
+        genus: FruitGenus::Cherry,
+        growth_time_secs: 4.0,
+        value: 1,
+        harvest_window_secs: 8.0,
+        fruit_image: asset_server.load("Crops/Cherry/cherry.png"),
+    });
+    fruit_map.species_vector.push(FruitSpecies {
+        genus: FruitGenus::Tomato,
+        growth_time_secs: 8.0,
+        value: 3,
+        harvest_window_secs: 14.0,
+        fruit_image: asset_server.load("Crops/Tomato/tomato.png"),
+    });
 }
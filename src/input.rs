@@ -0,0 +1,383 @@
+use std::{collections::BTreeMap, io::ErrorKind, path::Path};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_pancam::PanCam;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    placement::{Footprint, PlacementDrag},
+    tree::Tree,
+    ui::CurrentIntention,
+    units::{BuildingTypeId, BuildingTypeMap},
+    GameState,
+};
+
+/// Number of build menu entries that get a number key.
+pub const BUILD_SLOTS: u8 = 9;
+
+/// Where the player's bindings are saved. Edit it to remap controls.
+pub const ACTION_MAP_CONFIG_PATH: &str = "config/input.ron";
+
+/// Something the player can do, independent of which input triggers it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum InputAction {
+    /// Place the prospective building, or drag out a row of them.
+    Place,
+    /// Held while placing to keep the building selected afterwards.
+    RepeatPlacement,
+    /// Drop the current selection or prospective building.
+    Cancel,
+    /// Select the next placed building.
+    CycleBuildings,
+    /// Pick the n-th entry of the build menu.
+    SelectBuilding(u8),
+    Undo,
+    Redo,
+    /// Held to drag the camera. Only mouse bindings apply.
+    Pan,
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    /// The second key pressed while the first (usually a modifier) is held,
+    /// without Shift.
+    Chord(KeyCode, KeyCode),
+    /// Like [`Binding::Chord`], with Shift held as well.
+    ShiftChord(KeyCode, KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+}
+
+impl std::fmt::Display for Binding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{:?}", key),
+            Binding::Chord(held, key) => write!(f, "{:?}+{:?}", held, key),
+            Binding::ShiftChord(held, key) => write!(f, "{:?}+Shift+{:?}", held, key),
+            Binding::Mouse(button) => write!(f, "Mouse {:?}", button),
+            Binding::Gamepad(button) => write!(f, "Gamepad {:?}", button),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ActionMapConfigError {
+    #[error("Could not access the input config: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse the input config: {0}")]
+    Parse(#[from] ron::de::SpannedError),
+    #[error("Could not write the input config: {0}")]
+    Serialize(#[from] ron::Error),
+}
+
+/// Inputs bound to each action.
+#[derive(Resource, Serialize, Deserialize)]
+pub struct ActionMap {
+    pub bindings: BTreeMap<InputAction, Vec<Binding>>,
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        let mut map = ActionMap {
+            bindings: BTreeMap::new(),
+        };
+        map.bind(InputAction::Place, Binding::Mouse(MouseButton::Left));
+        map.bind(
+            InputAction::Place,
+            Binding::Gamepad(GamepadButtonType::South),
+        );
+        map.bind(
+            InputAction::RepeatPlacement,
+            Binding::Key(KeyCode::ShiftLeft),
+        );
+        map.bind(
+            InputAction::RepeatPlacement,
+            Binding::Key(KeyCode::ShiftRight),
+        );
+        map.bind(InputAction::Cancel, Binding::Key(KeyCode::Escape));
+        map.bind(InputAction::Cancel, Binding::Mouse(MouseButton::Right));
+        map.bind(
+            InputAction::Cancel,
+            Binding::Gamepad(GamepadButtonType::East),
+        );
+        map.bind(InputAction::CycleBuildings, Binding::Key(KeyCode::Tab));
+        let digits = [
+            KeyCode::Digit1,
+            KeyCode::Digit2,
+            KeyCode::Digit3,
+            KeyCode::Digit4,
+            KeyCode::Digit5,
+            KeyCode::Digit6,
+            KeyCode::Digit7,
+            KeyCode::Digit8,
+            KeyCode::Digit9,
+        ];
+        for (slot, key) in (0..BUILD_SLOTS).zip(digits) {
+            map.bind(InputAction::SelectBuilding(slot), Binding::Key(key));
+        }
+        for ctrl in [KeyCode::ControlLeft, KeyCode::ControlRight] {
+            map.bind(InputAction::Undo, Binding::Chord(ctrl, KeyCode::KeyZ));
+            map.bind(InputAction::Redo, Binding::Chord(ctrl, KeyCode::KeyY));
+            map.bind(InputAction::Redo, Binding::ShiftChord(ctrl, KeyCode::KeyZ));
+        }
+        map.bind(InputAction::Pan, Binding::Mouse(MouseButton::Middle));
+        map.bind(InputAction::MoveUp, Binding::Key(KeyCode::KeyW));
+        map.bind(
+            InputAction::MoveUp,
+            Binding::Gamepad(GamepadButtonType::DPadUp),
+        );
+        map.bind(InputAction::MoveDown, Binding::Key(KeyCode::KeyS));
+        map.bind(
+            InputAction::MoveDown,
+            Binding::Gamepad(GamepadButtonType::DPadDown),
+        );
+        map.bind(InputAction::MoveLeft, Binding::Key(KeyCode::KeyA));
+        map.bind(
+            InputAction::MoveLeft,
+            Binding::Gamepad(GamepadButtonType::DPadLeft),
+        );
+        map.bind(InputAction::MoveRight, Binding::Key(KeyCode::KeyD));
+        map.bind(
+            InputAction::MoveRight,
+            Binding::Gamepad(GamepadButtonType::DPadRight),
+        );
+        map
+    }
+}
+
+impl ActionMap {
+    pub fn bind(&mut self, action: InputAction, binding: Binding) {
+        self.bindings.entry(action).or_default().push(binding);
+    }
+
+    pub fn bindings(&self, action: InputAction) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn load(path: &str) -> Result<Self, ActionMapConfigError> {
+        let bytes = std::fs::read(path)?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), ActionMapConfigError> {
+        if let Some(dir) = Path::new(path).parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = ron::ser::to_string_pretty(self, PrettyConfig::default())?;
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// Human readable list of the inputs bound to `action`, if any.
+    pub fn describe(&self, action: InputAction) -> Option<String> {
+        let bindings = self.bindings(action);
+        if bindings.is_empty() {
+            return None;
+        }
+        let names: Vec<_> = bindings.iter().map(Binding::to_string).collect();
+        Some(names.join(" or "))
+    }
+
+    /// Reads the saved bindings over the defaults. Actions missing from the
+    /// saved file keep their default bindings. Without a saved file the
+    /// defaults are written out so they can be edited.
+    pub fn load_or_default(path: &str) -> Self {
+        match Self::load(path) {
+            Ok(saved) => {
+                let mut map = Self::default();
+                map.bindings.extend(saved.bindings);
+                map
+            }
+            Err(ActionMapConfigError::Io(err)) if err.kind() == ErrorKind::NotFound => {
+                let map = Self::default();
+                if let Err(err) = map.save(path) {
+                    warn!("Could not write default controls: {}", err);
+                }
+                map
+            }
+            Err(err) => {
+                warn!("Using default controls: {}", err);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn mouse_buttons(&self, action: InputAction) -> Vec<MouseButton> {
+        self.bindings(action)
+            .iter()
+            .filter_map(|binding| match *binding {
+                Binding::Mouse(button) => Some(button),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Reads [`InputAction`]s through the current [`ActionMap`].
+#[derive(SystemParam)]
+pub struct ActionInput<'w> {
+    pub map: Res<'w, ActionMap>,
+    keys: Res<'w, ButtonInput<KeyCode>>,
+    mouse: Res<'w, ButtonInput<MouseButton>>,
+    gamepads: Res<'w, Gamepads>,
+    gamepad_buttons: Res<'w, ButtonInput<GamepadButton>>,
+}
+
+impl<'w> ActionInput<'w> {
+    fn any_gamepad(
+        &self,
+        button: GamepadButtonType,
+        check: impl Fn(GamepadButton) -> bool,
+    ) -> bool {
+        self.gamepads
+            .iter()
+            .any(|gamepad| check(GamepadButton::new(gamepad, button)))
+    }
+
+    /// Whether `held` is down and Shift is down exactly when `shift` asks for it.
+    fn chord_held(&self, held: KeyCode, shift: bool) -> bool {
+        let shift_down = self
+            .keys
+            .any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        self.keys.pressed(held) && shift_down == shift
+    }
+
+    pub fn pressed(&self, action: InputAction) -> bool {
+        self.map
+            .bindings(action)
+            .iter()
+            .any(|binding| match *binding {
+                Binding::Key(key) => self.keys.pressed(key),
+                Binding::Chord(held, key) => self.chord_held(held, false) && self.keys.pressed(key),
+                Binding::ShiftChord(held, key) => {
+                    self.chord_held(held, true) && self.keys.pressed(key)
+                }
+                Binding::Mouse(button) => self.mouse.pressed(button),
+                Binding::Gamepad(button) => {
+                    self.any_gamepad(button, |b| self.gamepad_buttons.pressed(b))
+                }
+            })
+    }
+
+    pub fn just_pressed(&self, action: InputAction) -> bool {
+        self.map
+            .bindings(action)
+            .iter()
+            .any(|binding| match *binding {
+                Binding::Key(key) => self.keys.just_pressed(key),
+                Binding::Chord(held, key) => {
+                    self.chord_held(held, false) && self.keys.just_pressed(key)
+                }
+                Binding::ShiftChord(held, key) => {
+                    self.chord_held(held, true) && self.keys.just_pressed(key)
+                }
+                Binding::Mouse(button) => self.mouse.just_pressed(button),
+                Binding::Gamepad(button) => {
+                    self.any_gamepad(button, |b| self.gamepad_buttons.just_pressed(b))
+                }
+            })
+    }
+
+    pub fn just_released(&self, action: InputAction) -> bool {
+        self.map
+            .bindings(action)
+            .iter()
+            .any(|binding| match *binding {
+                Binding::Key(key) | Binding::Chord(_, key) | Binding::ShiftChord(_, key) => {
+                    self.keys.just_released(key)
+                }
+                Binding::Mouse(button) => self.mouse.just_released(button),
+                Binding::Gamepad(button) => {
+                    self.any_gamepad(button, |b| self.gamepad_buttons.just_released(b))
+                }
+            })
+    }
+}
+
+/// Keeps the camera's drag buttons in line with the [`InputAction::Pan`]
+/// bindings.
+pub fn sys_sync_pancam_bindings(map: Res<ActionMap>, mut cameras: Query<&mut PanCam>) {
+    for mut pancam in cameras.iter_mut() {
+        pancam.grab_buttons = map.mouse_buttons(InputAction::Pan);
+    }
+}
+
+pub fn sys_save_action_map(map: Res<ActionMap>) {
+    if let Err(err) = map.save(ACTION_MAP_CONFIG_PATH) {
+        warn!("Could not save controls: {}", err);
+    }
+}
+
+pub fn sys_intention_hotkeys(
+    mut commands: Commands,
+    input: ActionInput,
+    current: Res<CurrentIntention>,
+    building_types: Res<BuildingTypeMap>,
+    mut drag: ResMut<PlacementDrag>,
+    placed: Query<(Entity, &BuildingTypeId), (With<Footprint>, Without<Tree>)>,
+) {
+    if input.just_pressed(InputAction::Cancel) {
+        drag.0 = None;
+        if *current != CurrentIntention::None {
+            commands.insert_resource(CurrentIntention::None);
+        }
+        return;
+    }
+
+    for slot in 0..BUILD_SLOTS {
+        if !input.just_pressed(InputAction::SelectBuilding(slot)) {
+            continue;
+        }
+        if let Some((typ, _)) = building_types.iter().nth(slot as usize) {
+            drag.0 = None;
+            commands.insert_resource(CurrentIntention::Prospective(typ));
+        }
+        return;
+    }
+
+    if input.just_pressed(InputAction::CycleBuildings) {
+        let mut buildings: Vec<_> = placed.iter().collect();
+        if buildings.is_empty() {
+            return;
+        }
+        buildings.sort_by_key(|(entity, _)| *entity);
+        let next = match *current {
+            CurrentIntention::Command(_, selected) | CurrentIntention::Inspect(_, selected) => {
+                buildings
+                    .iter()
+                    .position(|(entity, _)| *entity > selected)
+                    .unwrap_or(0)
+            }
+            _ => 0,
+        };
+        let (entity, typ) = buildings[next];
+        commands.insert_resource(CurrentIntention::Command(typ.0, entity));
+    }
+}
+
+pub struct ActionInputPlugin;
+
+impl Plugin for ActionInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ActionMap::load_or_default(ACTION_MAP_CONFIG_PATH))
+            .add_systems(
+                Update,
+                (
+                    sys_intention_hotkeys.run_if(in_state(GameState::Playing)),
+                    sys_sync_pancam_bindings.run_if(resource_changed::<ActionMap>),
+                    // Inserting the map counts as a change; only save edits
+                    // made while running.
+                    sys_save_action_map.run_if(
+                        resource_changed::<ActionMap>.and_then(not(resource_added::<ActionMap>)),
+                    ),
+                ),
+            );
+    }
+}
@@ -1,8 +1,15 @@
 use crate::ui::OverlayTilemapMaterial;
 
-use super::GameState;
-use bevy::prelude::*;
+use super::{
+    units::{
+        definition::{BuildingDefinitionsApplied, BuildingDefinitionsHandle},
+        wave_script::WaveScriptHandle,
+    },
+    GameState,
+};
+use bevy::{asset::LoadState, prelude::*};
 use bevy_ecs_tilemap::tiles::TilePos;
+use bevy_rapier2d::prelude::{Collider, RigidBody};
 use pathfinding::{directed::dfs, grid::Grid};
 use thiserror::Error;
 
@@ -31,6 +38,9 @@ pub struct LoadingLevel(pub Handle<Image>);
 
 pub(crate) fn kickoff_load(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands.insert_resource(LoadingLevel(asset_server.load("levels/level1.png")));
+    commands.insert_resource(WaveScriptHandle(
+        asset_server.load("levels/level1.waves.ron"),
+    ));
 }
 
 #[derive(Error, Debug)]
@@ -70,12 +80,59 @@ fn compute_path_from_grid(grid_data: Grid) -> Result<TilePath, TileMapError> {
     })
 }
 
+/// Static walls around the edge of the map so that fallen fruit can't roll off.
+fn spawn_map_bounds(
+    commands: &mut Commands,
+    tilemap_entity: Entity,
+    map_size: &bevy_ecs_tilemap::map::TilemapSize,
+    grid_size: &bevy_ecs_tilemap::map::TilemapGridSize,
+) {
+    const WALL_HALF_THICKNESS: f32 = 4.0;
+    let width = map_size.x as f32 * grid_size.x;
+    let height = map_size.y as f32 * grid_size.y;
+    // Tile centres start at the tilemap origin, so the map edge is half a
+    // tile further out.
+    let min = Vec2::new(-grid_size.x, -grid_size.y) / 2.0;
+    let center = min + Vec2::new(width, height) / 2.0;
+    let walls = [
+        (
+            Vec2::new(center.x, min.y - WALL_HALF_THICKNESS),
+            Vec2::new(width / 2.0, WALL_HALF_THICKNESS),
+        ),
+        (
+            Vec2::new(center.x, min.y + height + WALL_HALF_THICKNESS),
+            Vec2::new(width / 2.0, WALL_HALF_THICKNESS),
+        ),
+        (
+            Vec2::new(min.x - WALL_HALF_THICKNESS, center.y),
+            Vec2::new(WALL_HALF_THICKNESS, height / 2.0),
+        ),
+        (
+            Vec2::new(min.x + width + WALL_HALF_THICKNESS, center.y),
+            Vec2::new(WALL_HALF_THICKNESS, height / 2.0),
+        ),
+    ];
+    commands.entity(tilemap_entity).with_children(|parent| {
+        for (position, half_extents) in walls {
+            parent.spawn((
+                TransformBundle::from_transform(Transform::from_translation(position.extend(0.0))),
+                RigidBody::Fixed,
+                Collider::cuboid(half_extents.x, half_extents.y),
+            ));
+        }
+    });
+}
+
 #[derive(Resource)]
 pub struct OverlayMaterialResource(pub Handle<OverlayTilemapMaterial>);
 
 pub(crate) fn sys_wait_for_loading_level(
     mut commands: Commands,
     loading_level: Res<LoadingLevel>,
+    wave_script: Res<WaveScriptHandle>,
+    building_definitions: Res<BuildingDefinitionsHandle>,
+    definitions_applied: Option<Res<BuildingDefinitionsApplied>>,
+    mut reported_failure: Local<bool>,
     images: Res<Assets<Image>>,
     asset_server: Res<AssetServer>,
     mut next_game_state: ResMut<NextState<GameState>>,
@@ -88,6 +145,25 @@ pub(crate) fn sys_wait_for_loading_level(
     let Some(image_data) = images.get(level_data_handle) else {
         return;
     };
+    // Lives, waves and building stats all come from these files, so the
+    // level can't start without them. A broken file keeps the game loading.
+    let failed = [
+        asset_server.load_state(&wave_script.0),
+        asset_server.load_state(&building_definitions.0),
+    ]
+    .into_iter()
+    .any(|state| matches!(state, LoadState::Failed(_)));
+    if failed {
+        if !*reported_failure {
+            error!("Level data failed to load, see the asset errors above");
+            *reported_failure = true;
+        }
+        return;
+    }
+    if asset_server.load_state(&wave_script.0) != LoadState::Loaded || definitions_applied.is_none()
+    {
+        return;
+    }
 
     let texture_handle: Handle<Image> = asset_server.load("tiles.png");
     let map_size = TilemapSize {
@@ -147,6 +223,8 @@ pub(crate) fn sys_wait_for_loading_level(
         tile_path,
     ));
 
+    spawn_map_bounds(&mut commands, tilemap_entity, &map_size, &grid_size);
+
     commands.insert_resource(OverlayMaterialResource(overlay_material));
     commands.remove_resource::<LoadingLevel>();
     next_game_state.set(GameState::Playing);
@@ -4,7 +4,6 @@
 // Feel free to delete this line.
 #![allow(clippy::too_many_arguments, clippy::type_complexity)]
 
-use std::any::TypeId;
 use std::time::Duration;
 
 use bevy::asset::AssetMetaCheck;
@@ -14,7 +13,7 @@ use bevy_ecs_tilemap::map::{TilemapGridSize, TilemapSize, TilemapType};
 use bevy_ecs_tilemap::tiles::TilePos;
 use bevy_ecs_tilemap::TilemapPlugin;
 use bevy_egui::EguiPlugin;
-use bevy_mod_picking::pointer::{InputPress, PointerButton, PointerId, PointerLocation};
+use bevy_mod_picking::pointer::{PointerId, PointerLocation};
 use bevy_mod_picking::selection::SelectionPluginSettings;
 use bevy_mod_picking::DefaultPickingPlugins;
 use bevy_pancam::{PanCam, PanCamPlugin};
@@ -23,18 +22,26 @@ use bevy_rapier2d::render::RapierDebugRenderPlugin;
 use bevy_spatial::{AutomaticUpdate, SpatialStructure, TransformMode};
 use construction_preview::BuildingPreviewPlugin;
 use fruit_type::FruitSpeciesPlugin;
+use input::{ActionInput, ActionMap, InputAction};
 use nutrients::NutrientPlugin;
+use placement::{PlacementDrag, PlacementParam, PlacementPlugin};
+use tree::TreePlugin;
 use ui::{CurrentIntention, OverlayMode};
 use units::{
-    BuildingTypeMap, BuildingTypePlugin, CurrentWave, DebugPlantType, IntermissionTimer,
-    NextWaveQueue,
+    construction::begin_construction,
+    demolish::Investment,
+    history::{record_action, BuildAction, BuildingSnapshot},
+    BuildingTypeId, BuildingTypeMap, BuildingTypePlugin, CurrentWave, IntermissionTimer,
+    NextWaveQueue, ReserveTrees,
 };
 
 mod construction_preview;
 mod fruit;
 mod fruit_type;
+mod input;
 mod level;
 mod nutrients;
+mod placement;
 mod tree;
 mod ui;
 mod units;
@@ -88,13 +95,18 @@ fn main() {
         .add_plugins(BuildingTypePlugin)
         .add_plugins(FruitSpeciesPlugin)
         .add_plugins(BuildingPreviewPlugin)
+        .add_plugins(TreePlugin)
+        .add_plugins(PlacementPlugin)
+        .add_plugins(input::ActionInputPlugin)
         .add_plugins(ui::UiPlugin)
         .add_plugins(voting::VotingPlugin)
         .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
         .add_plugins(RapierDebugRenderPlugin::default())
         .insert_resource(Score(0))
+        .insert_resource(Funds(STARTING_FUNDS))
         .insert_resource(CurrentIntention::None)
         .insert_resource(NextWaveQueue::default())
+        .insert_resource(ReserveTrees::default())
         .insert_resource(Level::default())
         .init_state::<GameState>()
         .add_sub_state::<PlayState>()
@@ -104,15 +116,19 @@ fn main() {
             Update,
             (
                 (ui::main_menu).run_if(in_state(GameState::MainMenu)),
+                ui::sys_game_over_summary.run_if(in_state(GameState::GameOver)),
                 (
                     sys_spawn_on_click,
                     fruit::sys_fruit_branch_spawn_fruit,
                     fruit::sys_fruit_grow,
+                    fruit::sys_fruit_flight,
+                    fruit::sys_fallen_fruit_rot,
                     ui::scoreboard,
                     ui::sys_ui_build_board,
                     ui::sys_selected_unit_ui.run_if(not(resource_equals(CurrentIntention::None))),
                     ui::sys_update_ui_title,
                     ui::sys_show_overlay,
+                    ui::sys_ui_wave_planner.run_if(in_state(PlayState::Intermission)),
                 )
                     .run_if(
                         in_state(GameState::Playing)
@@ -133,14 +149,15 @@ fn main() {
             ),
         )
         .observe(fruit::obs_fruit_harvested)
+        .observe(fruit::obs_fruit_knocked_off)
         .run();
 }
 
-fn setup(mut commands: Commands) {
+fn setup(mut commands: Commands, action_map: Res<ActionMap>) {
     commands.spawn((
         Camera2dBundle::default(),
         PanCam {
-            grab_buttons: vec![MouseButton::Middle],
+            grab_buttons: action_map.mouse_buttons(InputAction::Pan),
             enabled: true,
             ..Default::default()
         },
@@ -149,30 +166,14 @@ fn setup(mut commands: Commands) {
 
 pub(crate) fn setup_game(
     mut commands: Commands,
-    buildings: Res<BuildingTypeMap>,
     mut next_play_state: ResMut<NextState<PlayState>>,
 ) {
-    let tree_type = buildings
-        .type_map
-        .get(&TypeId::of::<DebugPlantType>())
-        .unwrap();
-    let mut initial_unit_queue = vec![];
-    for _ in 0..10 {
-        let target = commands
-            .spawn(SpatialBundle {
-                transform: Transform::from_xyz(-10000.0, 0., 0.),
-                visibility: Visibility::Hidden,
-                ..Default::default()
-            })
-            .id();
-        tree_type.construct_building(&mut commands, target);
-        commands.entity(target).insert(Visibility::Hidden);
-        initial_unit_queue.push(target);
-    }
-    commands.insert_resource(NextWaveQueue(initial_unit_queue));
-    commands.insert_resource(CurrentWave::new(Duration::from_secs(1)));
+    commands.insert_resource(Funds(STARTING_FUNDS));
+    // The wave script sends in the first trees when the wave starts.
+    commands.insert_resource(NextWaveQueue::default());
+    commands.insert_resource(CurrentWave::default());
     commands.insert_resource(IntermissionTimer(Timer::new(
-        Duration::from_secs(3),
+        Duration::from_secs(15),
         TimerMode::Once,
     )));
 
@@ -216,77 +217,131 @@ pub type MapQuery<'w, 's> = Query<
 >;
 
 pub trait MapQueryHelpers {
-    fn snap_to_tile_center(&self, pos: &Vec2) -> Option<Vec3>;
-    fn tile_center_to_corner(&self) -> Vec3;
+    fn tile_pos_at(&self, pos: &Vec2) -> Option<TilePos>;
+    fn snap_footprint(&self, pos: &Vec2, size: UVec2) -> Option<(Vec3, IVec2)>;
 }
 
 impl<'w, 's> MapQueryHelpers for MapQuery<'w, 's> {
-    fn snap_to_tile_center(&self, pos: &Vec2) -> Option<Vec3> {
-        let Some((map_type, map_size, map_grid_size, map_transform)) = self.get_single().ok()
-        else {
-            warn!("Map data not available for placing buildings");
-            return None;
-        };
-        let clicked_tile = TilePos::from_world_pos(
+    fn tile_pos_at(&self, pos: &Vec2) -> Option<TilePos> {
+        let (map_type, map_size, map_grid_size, map_transform) = self.get_single().ok()?;
+        TilePos::from_world_pos(
             &(*pos - map_transform.translation().xy()),
             map_size,
             map_grid_size,
             map_type,
-        )?;
-        Some(
-            map_transform.translation()
-                + Vec3::from((clicked_tile.center_in_world(map_grid_size, map_type), 5.0)),
         )
     }
 
-    fn tile_center_to_corner(&self) -> Vec3 {
-        let Some((_, _, map_grid_size, _)) = self.get_single().ok() else {
+    /// Snaps a footprint of `size` tiles so that it is centred as close to
+    /// `pos` as possible. Odd sizes centre on a tile, even sizes on a tile
+    /// edge. Returns the world position of the footprint centre and the
+    /// (possibly off-map) tile coordinate of its bottom-left corner.
+    fn snap_footprint(&self, pos: &Vec2, size: UVec2) -> Option<(Vec3, IVec2)> {
+        let Some((_, _, map_grid_size, map_transform)) = self.get_single().ok() else {
             warn!("Map data not available for placing buildings");
-            return Vec3::ZERO;
+            return None;
         };
-        Vec3::new(map_grid_size.x, -map_grid_size.y, 0.0) / 2.0
+        let grid = Vec2::new(map_grid_size.x, map_grid_size.y);
+        // Tile coordinates in which tile `i` spans `[i, i + 1)`.
+        let local = (*pos - map_transform.translation().xy()) / grid + Vec2::splat(0.5);
+        let size = size.as_vec2();
+        let min_tile = (local - size / 2.0).round().as_ivec2();
+        let center = (min_tile.as_vec2() + size / 2.0 - Vec2::splat(0.5)) * grid;
+        Some((map_transform.translation() + center.extend(5.0), min_tile))
     }
 }
 
 pub fn sys_spawn_on_click(
     mut commands: Commands,
-    mut press_events: EventReader<InputPress>,
     pointers: CameraPointerParam,
     current_inspector: Res<CurrentIntention>,
-    map_query: MapQuery,
+    mut placement: PlacementParam,
+    mut drag: ResMut<PlacementDrag>,
+    mut funds: ResMut<Funds>,
     building_types: Res<BuildingTypeMap>,
+    input: ActionInput,
 ) {
-    for press in press_events
-        .read()
-        .filter(|p| p.is_just_down(PointerButton::Primary))
-    {
-        let Some(pos) = pointers.get_world_pointer_location(press.pointer_id) else {
-            continue;
-        };
-        if let CurrentIntention::Prospective(ref building_type_id) = *current_inspector {
-            let Some(building_type) = building_types.type_map.get(building_type_id) else {
-                info!("Propective building type was not found");
-                continue;
-            };
-            let Some(mut map_pos) = map_query.snap_to_tile_center(&pos) else {
-                continue;
-            };
-            map_pos += map_query.tile_center_to_corner();
-            let new_entity = commands
-                .spawn(SpatialBundle {
-                    transform: Transform::from_translation(map_pos),
-                    ..Default::default()
-                })
-                .id();
-            building_type.construct_building(&mut commands, new_entity);
-            commands.insert_resource(CurrentIntention::None);
+    let CurrentIntention::Prospective(building_type_id) = *current_inspector else {
+        drag.0 = None;
+        return;
+    };
+    let Some(pos) = pointers.get_world_pointer_location(PointerId::Mouse) else {
+        return;
+    };
+    if input.just_pressed(InputAction::Place) {
+        drag.0 = Some(pos);
+        return;
+    }
+    if !input.just_released(InputAction::Place) {
+        return;
+    }
+    let Some(start) = drag.0.take() else {
+        return;
+    };
+    let Some(building_type) = building_types.type_map.get(&building_type_id) else {
+        info!("Propective building type was not found");
+        return;
+    };
+    let mut placed_any = false;
+    for (_, site) in placement.plan_drag(start, pos, building_type.as_ref()) {
+        if funds.0 < building_type.cost() {
+            info!("Can't afford {}", building_type.name());
+            break;
         }
+        let site = match site {
+            Ok(site) => site,
+            Err(err) => {
+                info!("Can't place {}: {}", building_type.name(), err);
+                continue;
+            }
+        };
+        let new_entity = commands
+            .spawn(SpatialBundle {
+                transform: Transform::from_translation(site.translation),
+                ..Default::default()
+            })
+            .id();
+        begin_construction(
+            &mut commands,
+            new_entity,
+            building_type_id,
+            building_type.as_ref(),
+            site.size,
+        );
+        funds.0 -= building_type.cost();
+        placement.occupancy.occupy(&site.footprint, new_entity);
+        commands.entity(new_entity).insert((
+            site.footprint,
+            Investment(building_type.cost()),
+            BuildingTypeId(building_type_id),
+        ));
+        commands.add(move |world: &mut World| {
+            if let Some(snapshot) = BuildingSnapshot::capture(world, new_entity) {
+                record_action(
+                    world,
+                    BuildAction::Place {
+                        entity: new_entity,
+                        snapshot,
+                    },
+                );
+            }
+        });
+        placed_any = true;
+    }
+    if placed_any && !input.pressed(InputAction::RepeatPlacement) {
+        commands.insert_resource(CurrentIntention::None);
     }
 }
 
 #[derive(Resource, Deref, DerefMut)]
 pub struct Score(usize);
 
+/// Currency earned by harvesting and spent on buildings and upgrades.
+#[derive(Resource, Deref, DerefMut)]
+pub struct Funds(usize);
+
+pub const STARTING_FUNDS: usize = 40;
+
 #[derive(Resource, Default, Deref, DerefMut)]
 pub struct Level {
     level: usize,
@@ -1,22 +1,70 @@
+use std::f32::consts::TAU;
+
 use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TileStorage;
+
+use crate::{
+    level::{CurrentLevel, TilePassable, TilePath},
+    units::CurrentWave,
+    GameState, PlayState,
+};
+
+/// Path tiles between the middles of two dry stretches.
+pub const DRY_SPELL_PERIOD_TILES: f32 = 24.0;
 
-use crate::{level::TilePassable, GameState};
+/// How far along the path the dry stretches move each wave.
+pub const DRY_SPELL_DRIFT_TILES: f32 = 7.0;
 
 #[derive(Component)]
 pub struct TileWater(pub u32);
 
+impl TileWater {
+    pub const SATURATED: u32 = 1000;
+
+    pub fn saturation(&self) -> f32 {
+        (self.0 as f32 / Self::SATURATED as f32).min(1.0)
+    }
+}
+
 pub struct NutrientPlugin;
 
 pub fn sys_setup_nutrients(mut commands: Commands, tile_query: Query<(Entity, &TilePassable)>) {
     for (tile_ent, passable) in tile_query.iter() {
-        commands
-            .entity(tile_ent)
-            .insert(TileWater(if passable.0 { 1000 } else { 0 }));
+        commands.entity(tile_ent).insert(TileWater(if passable.0 {
+            TileWater::SATURATED
+        } else {
+            0
+        }));
+    }
+}
+
+/// Alternates wet and dry stretches along the path, shifted by the wave
+/// number, so drought tolerance matters for the trees walking it.
+pub fn sys_water_path(
+    current_wave: Res<CurrentWave>,
+    level_query: Query<(&TilePath, &TileStorage), With<CurrentLevel>>,
+    mut tile_water: Query<&mut TileWater>,
+) {
+    let Ok((TilePath { path }, tile_storage)) = level_query.get_single() else {
+        return;
+    };
+    let drift = current_wave.index as f32 * DRY_SPELL_DRIFT_TILES;
+    for (idx, tile_pos) in path.iter().enumerate() {
+        let Some(mut water) = tile_storage
+            .get(tile_pos)
+            .and_then(|tile_ent| tile_water.get_mut(tile_ent).ok())
+        else {
+            continue;
+        };
+        let phase = (idx as f32 + drift) / DRY_SPELL_PERIOD_TILES * TAU;
+        let saturation = 0.5 + 0.5 * phase.cos();
+        water.0 = (saturation * TileWater::SATURATED as f32) as u32;
     }
 }
 
 impl Plugin for NutrientPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::Playing), sys_setup_nutrients);
+        app.add_systems(OnEnter(GameState::Playing), sys_setup_nutrients)
+            .add_systems(OnEnter(PlayState::Intermission), sys_water_path);
     }
 }
@@ -0,0 +1,231 @@
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bevy_ecs_tilemap::{
+    map::TilemapSize,
+    tiles::{TilePos, TileStorage},
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    level::{CurrentLevel, TilePassable},
+    units::Building,
+    GameState, MapQuery, MapQueryHelpers,
+};
+
+/// Which terrain a building may be placed on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum PlacementRule {
+    /// Only on open ground, never on the walking path.
+    #[default]
+    OffPath,
+    /// Only on the walking path.
+    OnPath,
+    /// Any tile inside the map.
+    Anywhere,
+}
+
+#[derive(Error, Debug)]
+pub enum PlacementError {
+    #[error("The building would extend past the edge of the map.")]
+    OffMap,
+    #[error("The tile at ({}, {}) is already occupied.", .0.x, .0.y)]
+    Occupied(TilePos),
+    #[error("The building can't be placed on the terrain at ({}, {}).", .0.x, .0.y)]
+    Terrain(TilePos),
+    #[error("Map data is not available.")]
+    NoMap,
+}
+
+/// Which building, if any, occupies each map tile.
+#[derive(Resource, Default)]
+pub struct TileOccupancy(pub HashMap<TilePos, Entity>);
+
+/// Tiles covered by a placed building.
+#[derive(Component, Clone)]
+pub struct Footprint {
+    pub tiles: Vec<TilePos>,
+    pub size: UVec2,
+}
+
+impl TileOccupancy {
+    pub fn occupy(&mut self, footprint: &Footprint, building: Entity) {
+        for tile_pos in footprint.tiles.iter() {
+            self.0.insert(*tile_pos, building);
+        }
+    }
+
+    pub fn release(&mut self, footprint: &Footprint) {
+        for tile_pos in footprint.tiles.iter() {
+            self.0.remove(tile_pos);
+        }
+    }
+}
+
+/// Where a building would go for a given pointer position.
+pub struct PlacementSite {
+    /// World position of the footprint centre.
+    pub translation: Vec3,
+    /// World size of the footprint.
+    pub size: Vec2,
+    pub footprint: Footprint,
+}
+
+/// Where the pointer was pressed, while a placement drag is in progress.
+#[derive(Resource, Default)]
+pub struct PlacementDrag(pub Option<Vec2>);
+
+/// Pointer positions that fill the line or rectangle dragged from `start` to
+/// `end`, spaced `step` apart and starting at `start`.
+pub fn drag_positions(start: Vec2, end: Vec2, step: Vec2) -> Vec<Vec2> {
+    let delta = end - start;
+    let counts = (delta / step).abs().round().as_uvec2();
+    let direction = Vec2::new(delta.x.signum(), delta.y.signum()) * step;
+    let mut positions = Vec::with_capacity(((counts.x + 1) * (counts.y + 1)) as usize);
+    for x in 0..=counts.x {
+        for y in 0..=counts.y {
+            positions.push(start + direction * Vec2::new(x as f32, y as f32));
+        }
+    }
+    positions
+}
+
+#[derive(SystemParam)]
+pub struct PlacementParam<'w, 's> {
+    pub map_query: MapQuery<'w, 's>,
+    pub tile_storage:
+        Query<'w, 's, (&'static TileStorage, &'static TilemapSize), With<CurrentLevel>>,
+    pub passable: Query<'w, 's, &'static TilePassable>,
+    pub occupancy: ResMut<'w, TileOccupancy>,
+}
+
+impl<'w, 's> PlacementParam<'w, 's> {
+    /// Snaps a footprint of `size` tiles to the pointer position `pos` and
+    /// lists the tiles it covers.
+    pub fn site_at(&self, pos: &Vec2, size: UVec2) -> Result<PlacementSite, PlacementError> {
+        let Ok((_, map_size)) = self.tile_storage.get_single() else {
+            return Err(PlacementError::NoMap);
+        };
+        let Ok((_, _, grid_size, _)) = self.map_query.get_single() else {
+            return Err(PlacementError::NoMap);
+        };
+        let (translation, min_tile) = self
+            .map_query
+            .snap_footprint(pos, size)
+            .ok_or(PlacementError::NoMap)?;
+        let mut tiles = Vec::with_capacity((size.x * size.y) as usize);
+        for dx in 0..size.x as i32 {
+            for dy in 0..size.y as i32 {
+                let tile = min_tile + IVec2::new(dx, dy);
+                if tile.x < 0 || tile.y < 0 {
+                    return Err(PlacementError::OffMap);
+                }
+                let tile_pos = TilePos {
+                    x: tile.x as u32,
+                    y: tile.y as u32,
+                };
+                if !tile_pos.within_map_bounds(map_size) {
+                    return Err(PlacementError::OffMap);
+                }
+                tiles.push(tile_pos);
+            }
+        }
+        Ok(PlacementSite {
+            translation,
+            size: size.as_vec2() * Vec2::new(grid_size.x, grid_size.y),
+            footprint: Footprint { tiles, size },
+        })
+    }
+
+    /// Checks that `building` can be placed at the pointer position `pos`,
+    /// returning where it would go.
+    pub fn validate(
+        &self,
+        pos: &Vec2,
+        building: &dyn Building,
+    ) -> Result<PlacementSite, PlacementError> {
+        let site = self.site_at(pos, building.tile_size())?;
+        let Ok((tile_storage, _)) = self.tile_storage.get_single() else {
+            return Err(PlacementError::NoMap);
+        };
+        for tile_pos in site.footprint.tiles.iter() {
+            if self.occupancy.0.contains_key(tile_pos) {
+                return Err(PlacementError::Occupied(*tile_pos));
+            }
+            let on_path = tile_storage
+                .get(tile_pos)
+                .and_then(|tile_ent| self.passable.get(tile_ent).ok())
+                .is_some_and(|passable| passable.0);
+            let allowed = match building.placement_rule() {
+                PlacementRule::OffPath => !on_path,
+                PlacementRule::OnPath => on_path,
+                PlacementRule::Anywhere => true,
+            };
+            if !allowed {
+                return Err(PlacementError::Terrain(*tile_pos));
+            }
+        }
+        Ok(site)
+    }
+
+    /// Validates every building in a drag from `start` to `end`. Sites that
+    /// overlap an earlier one in the same drag are rejected. Each result is
+    /// paired with the pointer position it was computed for.
+    pub fn plan_drag(
+        &self,
+        start: Vec2,
+        end: Vec2,
+        building: &dyn Building,
+    ) -> Vec<(Vec2, Result<PlacementSite, PlacementError>)> {
+        let Ok((_, _, grid_size, _)) = self.map_query.get_single() else {
+            return vec![];
+        };
+        let step = building.tile_size().as_vec2() * Vec2::new(grid_size.x, grid_size.y);
+        let mut planned = HashSet::new();
+        drag_positions(start, end, step)
+            .into_iter()
+            .map(|pos| {
+                let site = self.validate(&pos, building).and_then(|site| {
+                    if let Some(tile_pos) =
+                        site.footprint.tiles.iter().find(|t| planned.contains(*t))
+                    {
+                        return Err(PlacementError::Occupied(*tile_pos));
+                    }
+                    planned.extend(site.footprint.tiles.iter().copied());
+                    Ok(site)
+                });
+                (pos, site)
+            })
+            .collect()
+    }
+}
+
+/// Stretches the sprite of a newly constructed building over its whole
+/// footprint, so that it is drawn and picked across every tile it occupies.
+pub fn sys_fit_sprite_to_footprint(
+    mut buildings: Query<(&Footprint, &mut Sprite), Added<Sprite>>,
+    map_query: MapQuery,
+) {
+    let Ok((_, _, grid_size, _)) = map_query.get_single() else {
+        return;
+    };
+    for (footprint, mut sprite) in buildings.iter_mut() {
+        sprite.custom_size = Some(footprint.size.as_vec2() * Vec2::new(grid_size.x, grid_size.y));
+    }
+}
+
+pub struct PlacementPlugin;
+
+impl Plugin for PlacementPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TileOccupancy>()
+            .init_resource::<PlacementDrag>()
+            .add_systems(
+                Update,
+                sys_fit_sprite_to_footprint.run_if(in_state(GameState::Playing)),
+            );
+    }
+}
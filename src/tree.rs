@@ -1,22 +1,129 @@
 use std::any::TypeId;
 
-use bevy::prelude::*;
+use bevy::{math::vec2, prelude::*};
 use bevy_mod_picking::{events::Pointer, prelude::On, selection::Select, PickableBundle};
+use rand::Rng;
 
-use crate::{fruit_type::FruitGenus, ui::CurrentIntention, units::DebugPlantType};
+use crate::{
+    fruit::{FruitBranch, FruitBranchBundle},
+    ui::CurrentIntention,
+    units::{DebugPlantType, NextWaveQueue, PathFollower, WaveCompletedEvent},
+    PlayState,
+};
+
+/// Trees closer than this (in world units) exchange pollen while walking the path.
+pub const POLLINATION_RANGE: f32 = 48.0;
+
+pub const MAX_FRUIT_YIELD: u32 = 5;
+
+/// Waves a tree can survive before it dies of old age.
+pub const MAX_TREE_AGE: u32 = 6;
+
+/// Scale a tree gains for every wave it survives.
+pub const AGE_GROWTH_PER_WAVE: f32 = 0.1;
+
+/// Fraction of its walking speed a tree loses for every wave it survives.
+pub const AGE_SLOWDOWN_PER_WAVE: f32 = 0.06;
+
+/// A surviving tree grows an extra fruit branch every this many waves.
+pub const WAVES_PER_NEW_BRANCH: u32 = 2;
+
+/// Heritable traits of a tree. Seeds dropped on harvest carry a mix of the
+/// parent tree's genome and whatever pollen it picked up.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreeGenome {
+    /// Index into `FruitSpeciesMap::species_vector`.
+    pub species: usize,
+    /// Multiplier applied to the species growth rate.
+    pub growth_speed: f32,
+    /// Number of fruit branches the tree grows.
+    pub fruit_yield: u32,
+    /// Fraction of its normal growth rate the tree keeps on completely dry soil.
+    pub drought_tolerance: f32,
+}
+
+impl Default for TreeGenome {
+    fn default() -> Self {
+        TreeGenome {
+            species: 0,
+            growth_speed: 1.0,
+            fruit_yield: 1,
+            drought_tolerance: 0.5,
+        }
+    }
+}
+
+impl TreeGenome {
+    pub fn wild(rng: &mut impl Rng, species: usize) -> Self {
+        TreeGenome {
+            species,
+            growth_speed: rng.gen_range(0.8..1.2),
+            fruit_yield: rng.gen_range(1..=2),
+            drought_tolerance: rng.gen_range(0.3..0.7),
+        }
+    }
+
+    /// Produces a child genome, picking or blending each trait from the two
+    /// parents with a small random mutation.
+    pub fn cross(&self, other: &TreeGenome, rng: &mut impl Rng) -> Self {
+        let species = if rng.gen_bool(0.5) {
+            self.species
+        } else {
+            other.species
+        };
+        let mut fruit_yield = if rng.gen_bool(0.5) {
+            self.fruit_yield
+        } else {
+            other.fruit_yield
+        };
+        if rng.gen_bool(0.1) {
+            fruit_yield = if rng.gen_bool(0.5) {
+                fruit_yield + 1
+            } else {
+                fruit_yield.saturating_sub(1)
+            };
+        }
+        let blend = rng.gen_range(0.0..=1.0);
+        TreeGenome {
+            species,
+            growth_speed: (self.growth_speed.lerp(other.growth_speed, blend)
+                * rng.gen_range(0.9..1.1))
+            .clamp(0.25, 4.0),
+            fruit_yield: fruit_yield.clamp(1, MAX_FRUIT_YIELD),
+            drought_tolerance: (self.drought_tolerance.lerp(other.drought_tolerance, blend)
+                + rng.gen_range(-0.05..0.05))
+            .clamp(0.0, 1.0),
+        }
+    }
+
+    /// Growth rate multiplier for a tree standing on soil with the given water
+    /// saturation (0.0 = dry, 1.0 = fully watered).
+    pub fn growth_rate(&self, water_saturation: f32) -> f32 {
+        let water = water_saturation.clamp(0.0, 1.0);
+        self.growth_speed * (water + (1.0 - water) * self.drought_tolerance)
+    }
+}
 
 #[derive(Component)]
 pub struct Tree {
-    #[allow(dead_code)]
-    pub genus: FruitGenus,
+    pub genome: TreeGenome,
+    /// Waves survived so far.
+    pub age: u32,
 }
 
+/// Genome of the last other tree this tree passed close to.
+#[derive(Component, Default)]
+pub struct Pollen(pub Option<TreeGenome>);
+
+/// Seeds collected from harvested fruit.
+#[derive(Resource, Default)]
+pub struct SeedBank(pub Vec<TreeGenome>);
+
 impl Tree {
-    pub fn new_bundle(texture: Handle<Image>) -> impl Bundle {
+    pub fn new_bundle(texture: Handle<Image>, genome: TreeGenome) -> impl Bundle {
         (
-            Tree {
-                genus: FruitGenus::Carrot,
-            },
+            Tree { genome, age: 0 },
+            Pollen::default(),
             texture,
             Sprite::default(),
             PickableBundle::default(),
@@ -28,4 +135,127 @@ impl Tree {
             }),
         )
     }
+
+    pub fn scale(&self) -> f32 {
+        1.0 + self.age as f32 * AGE_GROWTH_PER_WAVE
+    }
+
+    /// Multiplier applied to the wave's walking speed.
+    pub fn speed_multiplier(&self) -> f32 {
+        (1.0 - self.age as f32 * AGE_SLOWDOWN_PER_WAVE).max(0.5)
+    }
+
+    pub fn branch_count(&self) -> u32 {
+        (self.genome.fruit_yield + self.age / WAVES_PER_NEW_BRANCH).min(MAX_FRUIT_YIELD)
+    }
+
+    fn branch_bundle(species: usize, idx: u32, branch_count: u32) -> FruitBranchBundle {
+        FruitBranchBundle {
+            branch: FruitBranch { species },
+            sprite: SpriteBundle {
+                transform: Transform::from_translation(
+                    branch_offset(idx, branch_count).extend(0.0),
+                ),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Turns `target` into a tree with the given genome, growing one fruit
+    /// branch per point of `fruit_yield`.
+    pub fn construct(
+        commands: &mut Commands,
+        target: Entity,
+        texture: Handle<Image>,
+        genome: TreeGenome,
+    ) {
+        let species = genome.species;
+        let branch_count = genome.fruit_yield;
+        commands
+            .entity(target)
+            .insert(Tree::new_bundle(texture, genome))
+            .with_children(|child_commands| {
+                for idx in 0..branch_count {
+                    child_commands.spawn(Tree::branch_bundle(species, idx, branch_count));
+                }
+            });
+    }
+}
+
+fn branch_offset(idx: u32, branch_count: u32) -> Vec2 {
+    vec2(
+        (idx as f32 - (branch_count - 1) as f32 / 2.0) * 4.0,
+        (idx % 2) as f32 * 3.0,
+    )
+}
+
+/// Ages the trees that made it through the wave: they grow bigger, slower and
+/// gain branches. Trees older than `MAX_TREE_AGE` die and leave a seed behind.
+pub fn obs_age_surviving_trees(
+    _event: Trigger<WaveCompletedEvent>,
+    mut commands: Commands,
+    mut next_wave: ResMut<NextWaveQueue>,
+    mut seed_bank: ResMut<SeedBank>,
+    mut trees: Query<(&mut Tree, &Pollen, &mut Transform, &Children), Without<FruitBranch>>,
+    mut branches: Query<&mut Transform, With<FruitBranch>>,
+) {
+    let mut rng = rand::thread_rng();
+    next_wave.0.retain(|tree_ent| {
+        let Ok((mut tree, pollen, mut transform, children)) = trees.get_mut(*tree_ent) else {
+            return true;
+        };
+        tree.age += 1;
+        if tree.age > MAX_TREE_AGE {
+            let pollinator = pollen.0.as_ref().unwrap_or(&tree.genome);
+            seed_bank.0.push(tree.genome.cross(pollinator, &mut rng));
+            commands.entity(*tree_ent).despawn_recursive();
+            return false;
+        }
+        transform.scale = Vec3::splat(tree.scale());
+
+        let branch_count = tree.branch_count();
+        let mut grown = 0;
+        for branch_ent in children.iter() {
+            let Ok(mut branch_tfm) = branches.get_mut(*branch_ent) else {
+                continue;
+            };
+            branch_tfm.translation = branch_offset(grown, branch_count).extend(0.0);
+            grown += 1;
+        }
+        let species = tree.genome.species;
+        commands.entity(*tree_ent).with_children(|child_commands| {
+            for idx in grown..branch_count {
+                child_commands.spawn(Tree::branch_bundle(species, idx, branch_count));
+            }
+        });
+        true
+    });
+}
+
+pub fn sys_cross_pollinate(
+    mut trees: Query<(&Tree, &GlobalTransform, &mut Pollen), With<PathFollower>>,
+) {
+    let mut combinations = trees.iter_combinations_mut();
+    while let Some([(tree_a, gt_a, mut pollen_a), (tree_b, gt_b, mut pollen_b)]) =
+        combinations.fetch_next()
+    {
+        if gt_a.translation().xy().distance(gt_b.translation().xy()) > POLLINATION_RANGE {
+            continue;
+        }
+        pollen_a.0 = Some(tree_b.genome.clone());
+        pollen_b.0 = Some(tree_a.genome.clone());
+    }
+}
+
+pub struct TreePlugin;
+
+impl Plugin for TreePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SeedBank>()
+            .add_systems(
+                Update,
+                sys_cross_pollinate.run_if(in_state(PlayState::Wave)),
+            )
+            .observe(obs_age_surviving_trees);
+    }
 }
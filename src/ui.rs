@@ -11,10 +11,23 @@ use bevy_egui::{
 };
 
 use crate::{
+    construction_preview::PreviewCoverage,
+    fruit_type::FruitSpeciesMap,
+    input::{ActionMap, InputAction, BUILD_SLOTS},
     level::OverlayMaterialResource,
     nutrients::TileWater,
-    units::{BuildingTypeMap, IntermissionTimer},
-    voting, GameState, PlayState, Score,
+    tree::{SeedBank, Tree, TreeGenome, MAX_TREE_AGE},
+    units::{
+        demolish::{DemolishBuildingEvent, Investment},
+        escape::{EscapeStats, Lives},
+        harvester::{HarvestLoad, HarvestStats, Harvester, HarvesterWorker, TargetingPolicy},
+        history::{self, record_action, BuildAction, BuildHistory, BuildingState},
+        spawn_queued_tree,
+        upgrade::{UpgradeHarvesterEvent, UpgradeTiers, UpgradeTrack, MAX_UPGRADE_TIER},
+        BuildingTypeMap, CurrentWave, DebugPlantType, IntermissionTimer, NextWaveQueue,
+        ReserveTrees, TowerRange,
+    },
+    voting, Funds, GameState, PlayState, Score,
 };
 
 pub fn main_menu(
@@ -50,6 +63,9 @@ pub fn main_menu(
 pub fn scoreboard(
     mut contexts: EguiContexts,
     mut score: ResMut<Score>,
+    funds: Res<Funds>,
+    lives: Res<Lives>,
+    seed_bank: Res<SeedBank>,
     mut next_state: ResMut<NextState<GameState>>,
     mut overlay_mode: ResMut<OverlayMode>,
 ) {
@@ -64,6 +80,9 @@ pub fn scoreboard(
             ui.set_width(ui.available_width());
             ui.set_height(ui.available_height());
             ui.label(RichText::new(score_label).text_style(egui::TextStyle::Heading));
+            ui.label(format!("Funds: {}", funds.0));
+            ui.label(format!("Lives: {}", lives.0));
+            ui.label(format!("Seeds: {}", seed_bank.0.len()));
             if ui.button("Toggle Water Overlay").clicked() {
                 *overlay_mode = match *overlay_mode {
                     OverlayMode::Normal => OverlayMode::Water,
@@ -79,10 +98,42 @@ pub fn scoreboard(
         });
 }
 
+pub fn sys_game_over_summary(
+    mut contexts: EguiContexts,
+    score: Res<Score>,
+    lives: Res<Lives>,
+    escape_stats: Res<EscapeStats>,
+    current_wave: Option<Res<CurrentWave>>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    let heading = if lives.0 == 0 { "Defeat" } else { "Game Over" };
+    let waves_survived = current_wave.map_or(0, |wave| wave.index);
+    egui::Window::new(heading)
+        .anchor(Align2::CENTER_CENTER, vec2(0.0, 0.0))
+        .collapsible(false)
+        .movable(false)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(
+                RichText::new(format!("Score: {}", score.0)).text_style(egui::TextStyle::Heading),
+            );
+            ui.label(format!("Waves survived: {}", waves_survived));
+            ui.label(format!(
+                "Trees escaped with fruit: {}",
+                escape_stats.escaped_trees
+            ));
+            ui.label(format!("Fruit wasted: {}", escape_stats.wasted_fruit));
+            if ui.button("Quit").clicked() {
+                app_exit.send(AppExit::Success);
+            }
+        });
+}
+
 pub fn sys_ui_build_board(
     mut contexts: EguiContexts,
     mut commands: Commands,
     building_types: Res<BuildingTypeMap>,
+    funds: Res<Funds>,
 ) {
     egui::Window::new("Units")
         .anchor(Align2::LEFT_BOTTOM, vec2(180.0, 0.0))
@@ -96,9 +147,22 @@ pub fn sys_ui_build_board(
             egui::Grid::new("tower_options")
                 .num_columns(1)
                 .show(ui, |ui| {
-                    for (typ, built_type) in building_types.type_map.iter() {
-                        if ui.button(built_type.name()).clicked() {
-                            commands.insert_resource(CurrentIntention::Prospective(*typ));
+                    for (slot, (typ, built_type)) in building_types.iter().enumerate() {
+                        let label = if slot < BUILD_SLOTS as usize {
+                            format!(
+                                "{}: {} ({})",
+                                slot + 1,
+                                built_type.name(),
+                                built_type.cost()
+                            )
+                        } else {
+                            format!("{} ({})", built_type.name(), built_type.cost())
+                        };
+                        if ui
+                            .add_enabled(funds.0 >= built_type.cost(), egui::Button::new(label))
+                            .clicked()
+                        {
+                            commands.insert_resource(CurrentIntention::Prospective(typ));
                         }
                     }
                 });
@@ -116,12 +180,25 @@ pub enum CurrentIntention {
 
 pub fn sys_selected_unit_ui(
     mut contexts: EguiContexts,
+    mut commands: Commands,
+    funds: Res<Funds>,
     current: Res<CurrentIntention>,
     building_types: Res<BuildingTypeMap>,
+    fruit_map: Res<FruitSpeciesMap>,
+    trees: Query<&Tree>,
+    harvesters: Query<(&Harvester, &TowerRange)>,
+    workers: Query<(&HarvesterWorker, &HarvestLoad)>,
+    harvest_stats: Query<&HarvestStats>,
+    upgrades: Query<(&UpgradeTiers, Option<&Harvester>, Option<&HarvesterWorker>)>,
+    coverage: Res<PreviewCoverage>,
+    mut policies: Query<&mut TargetingPolicy>,
+    investments: Query<&Investment>,
+    action_map: Res<ActionMap>,
+    mut confirm_demolish: Local<Option<Entity>>,
 ) {
     match *current {
         CurrentIntention::None => {}
-        CurrentIntention::Inspect(type_id, _ent) => {
+        CurrentIntention::Inspect(type_id, ent) => {
             let Some(building) = building_types.type_map.get(&type_id) else {
                 return;
             };
@@ -132,19 +209,135 @@ pub fn sys_selected_unit_ui(
                 .resizable(false)
                 .show(contexts.ctx_mut(), |ui| {
                     ui.label(format!("Type: {}", building.name()));
+                    if let Ok(tree) = trees.get(ent) {
+                        let genome = &tree.genome;
+                        if let Some(species) = fruit_map.species_vector.get(genome.species) {
+                            ui.label(format!("Species: {:?}", species.genus));
+                        }
+                        ui.label(format!("Growth speed: {:.2}", genome.growth_speed));
+                        ui.label(format!("Yield: {}", genome.fruit_yield));
+                        ui.label(format!("Age: {}/{} waves", tree.age, MAX_TREE_AGE));
+                        ui.label(format!(
+                            "Drought tolerance: {:.0}%",
+                            genome.drought_tolerance * 100.0
+                        ));
+                    }
                 });
         }
-        CurrentIntention::Command(type_id, _ent) => {
+        CurrentIntention::Command(type_id, ent) => {
             let Some(building) = building_types.type_map.get(&type_id) else {
                 return;
             };
             egui::Window::new("Command Unit")
                 .collapsible(false)
                 .anchor(Align2::RIGHT_TOP, egui::vec2(0.0, 0.0))
-                .interactable(false)
                 .resizable(false)
                 .show(contexts.ctx_mut(), |ui| {
                     ui.label(format!("Type: {}", building.name()));
+                    if let Ok((harvester, range)) = harvesters.get(ent) {
+                        ui.label(format!("Range: {}", range.0));
+                        ui.label(format!("Cooldown: {:.1}s", harvester.cooldown_secs));
+                        ui.label(format!("Targets per pick: {}", harvester.targets_per_pick));
+                        ui.label(format!(
+                            "Harvest rate: {:.1} fruit/s",
+                            harvester.harvest_rate()
+                        ));
+                    }
+                    if let Ok((worker, load)) = workers.get(ent) {
+                        ui.label(format!(
+                            "Carrying: {}/{}",
+                            load.fruit_count, worker.capacity
+                        ));
+                        ui.label(format!("Load value: {}", load.value));
+                    }
+                    if let Ok(stats) = harvest_stats.get(ent) {
+                        ui.label(format!(
+                            "Collected: {} fruit ({} value)",
+                            stats.fruit_collected, stats.value_collected
+                        ));
+                    }
+                    if let Ok(mut policy) = policies.get_mut(ent) {
+                        let mut selected = *policy;
+                        egui::ComboBox::from_label("Targeting")
+                            .selected_text(selected.label())
+                            .show_ui(ui, |ui| {
+                                for option in TargetingPolicy::ALL {
+                                    ui.selectable_value(&mut selected, option, option.label());
+                                }
+                            });
+                        if selected != *policy {
+                            let before = BuildingState {
+                                policy: Some(*policy),
+                                ..Default::default()
+                            };
+                            let after = BuildingState {
+                                policy: Some(selected),
+                                ..Default::default()
+                            };
+                            *policy = selected;
+                            commands.add(move |world: &mut World| {
+                                record_action(
+                                    world,
+                                    BuildAction::Modify {
+                                        entity: ent,
+                                        before,
+                                        after,
+                                        cost: 0,
+                                    },
+                                );
+                            });
+                        }
+                    }
+                    if let Ok((tiers, harvester, worker)) = upgrades.get(ent) {
+                        ui.separator();
+                        ui.label("Upgrades");
+                        for track in UpgradeTrack::ALL {
+                            if !track.applies_to(harvester, worker) {
+                                continue;
+                            }
+                            ui.horizontal(|ui| {
+                                ui.label(format!(
+                                    "{} ({}/{})",
+                                    track.label(),
+                                    tiers.tier(track),
+                                    MAX_UPGRADE_TIER
+                                ));
+                                let Some(cost) = tiers.next_cost(track) else {
+                                    ui.label("Max");
+                                    return;
+                                };
+                                if ui
+                                    .add_enabled(
+                                        funds.0 >= cost,
+                                        egui::Button::new(format!("Upgrade ({})", cost)),
+                                    )
+                                    .clicked()
+                                {
+                                    commands.trigger_targets(UpgradeHarvesterEvent { track }, ent);
+                                }
+                            });
+                        }
+                    }
+                    if let Ok(investment) = investments.get(ent) {
+                        ui.separator();
+                        if *confirm_demolish == Some(ent) {
+                            ui.label(format!("Demolish for a refund of {}?", investment.refund()));
+                            ui.horizontal(|ui| {
+                                if ui.button("Confirm").clicked() {
+                                    commands.trigger_targets(DemolishBuildingEvent, ent);
+                                    *confirm_demolish = None;
+                                }
+                                if ui.button("Cancel").clicked() {
+                                    *confirm_demolish = None;
+                                }
+                            });
+                        } else if ui
+                            .button(format!("Demolish (refund {})", investment.refund()))
+                            .clicked()
+                        {
+                            *confirm_demolish = Some(ent);
+                        }
+                    }
                 });
         }
         CurrentIntention::Prospective(type_id) => {
@@ -158,11 +351,144 @@ pub fn sys_selected_unit_ui(
                 .resizable(false)
                 .show(contexts.ctx_mut(), |ui| {
                     ui.label(format!("Type: {}", building.name()));
+                    ui.label(format!("Cost: {} (funds: {})", building.cost(), funds.0));
+                    if let Some(range) = building.range() {
+                        ui.label(format!("Range: {}", range));
+                    }
+                    if let Some(covered) = coverage.0 {
+                        ui.label(format!("Path tiles in reach: {}", covered));
+                    }
+                    match action_map.describe(InputAction::RepeatPlacement) {
+                        Some(keys) => ui.label(format!(
+                            "Drag to place a row, hold {} to keep placing",
+                            keys
+                        )),
+                        None => ui.label("Drag to place a row"),
+                    };
                 });
         }
     }
 }
 
+fn describe_tree(tree: &Tree, fruit_map: &FruitSpeciesMap) -> String {
+    format!(
+        "{}, age {}/{}",
+        describe_genome(&tree.genome, fruit_map),
+        tree.age,
+        MAX_TREE_AGE
+    )
+}
+
+fn describe_genome(genome: &TreeGenome, fruit_map: &FruitSpeciesMap) -> String {
+    let species = fruit_map
+        .species_vector
+        .get(genome.species)
+        .map_or_else(|| "Unknown".to_string(), |s| format!("{:?}", s.genus));
+    format!(
+        "{} (growth {:.2}, yield {}, drought {:.0}%)",
+        species,
+        genome.growth_speed,
+        genome.fruit_yield,
+        genome.drought_tolerance * 100.0
+    )
+}
+
+pub fn sys_ui_wave_planner(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut seed_bank: ResMut<SeedBank>,
+    mut next_wave: ResMut<NextWaveQueue>,
+    mut reserve: ResMut<ReserveTrees>,
+    mut intermission_timer: ResMut<IntermissionTimer>,
+    building_types: Res<BuildingTypeMap>,
+    fruit_map: Res<FruitSpeciesMap>,
+    trees: Query<&Tree>,
+    history: Res<BuildHistory>,
+) {
+    let mut hold_back = None;
+    let mut send = None;
+    let mut plant = None;
+    egui::Window::new("Plan Next Wave")
+        .anchor(Align2::RIGHT_BOTTOM, vec2(0.0, 0.0))
+        .collapsible(false)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(400.0)
+                .show(ui, |ui| {
+                    ui.heading(format!("Next wave ({})", next_wave.0.len()));
+                    for (idx, tree_ent) in next_wave.0.iter().enumerate() {
+                        let Ok(tree) = trees.get(*tree_ent) else {
+                            continue;
+                        };
+                        ui.horizontal(|ui| {
+                            ui.label(describe_tree(tree, &fruit_map));
+                            if ui.button("Hold back").clicked() {
+                                hold_back = Some(idx);
+                            }
+                        });
+                    }
+                    ui.separator();
+                    ui.heading(format!("Reserve ({})", reserve.0.len()));
+                    for (idx, tree_ent) in reserve.0.iter().enumerate() {
+                        let Ok(tree) = trees.get(*tree_ent) else {
+                            continue;
+                        };
+                        ui.horizontal(|ui| {
+                            ui.label(describe_tree(tree, &fruit_map));
+                            if ui.button("Send").clicked() {
+                                send = Some(idx);
+                            }
+                        });
+                    }
+                    ui.separator();
+                    ui.heading(format!("Seeds ({})", seed_bank.0.len()));
+                    for (idx, genome) in seed_bank.0.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(describe_genome(genome, &fruit_map));
+                            if ui.button("Plant").clicked() {
+                                plant = Some(idx);
+                            }
+                        });
+                    }
+                });
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(history.can_undo(), egui::Button::new("Undo"))
+                    .clicked()
+                {
+                    commands.add(history::undo);
+                }
+                if ui
+                    .add_enabled(history.can_redo(), egui::Button::new("Redo"))
+                    .clicked()
+                {
+                    commands.add(history::redo);
+                }
+            });
+            if ui.button("Start Wave").clicked() {
+                let duration = intermission_timer.0.duration();
+                intermission_timer.0.set_elapsed(duration);
+            }
+        });
+
+    if let Some(idx) = hold_back {
+        reserve.0.push(next_wave.0.remove(idx));
+    }
+    if let Some(idx) = send {
+        next_wave.0.push(reserve.0.remove(idx));
+    }
+    if let Some(idx) = plant {
+        let Some(tree_type) = building_types.type_map.get(&TypeId::of::<DebugPlantType>()) else {
+            return;
+        };
+        let genome = seed_bank.0.remove(idx);
+        let tree_ent = spawn_queued_tree(&mut commands, tree_type.sprite_image().clone(), genome);
+        next_wave.0.push(tree_ent);
+    }
+}
+
 #[derive(Component)]
 pub struct UiTitleMessage;
 
@@ -246,7 +572,9 @@ pub fn sys_update_ui_title(
     mut query: Query<&mut Text, With<UiTitleMessage>>,
     play_state: Res<State<PlayState>>,
     intermission_timer: Res<IntermissionTimer>,
+    current_wave: Option<Res<CurrentWave>>,
 ) {
+    let wave_number = current_wave.map_or(1, |wave| wave.index + 1);
     let Ok(mut text_node) = query.get_single_mut() else {
         warn!("UI title Text node not found");
         return;
@@ -254,7 +582,8 @@ pub fn sys_update_ui_title(
     match play_state.get() {
         PlayState::Intermission => {
             text_node.sections[0].value = format!(
-                "Next Wave in {} secs",
+                "Wave {} in {} secs",
+                wave_number,
                 (intermission_timer.0.duration() - intermission_timer.0.elapsed()).as_secs_f32()
             );
         }
@@ -262,7 +591,7 @@ pub fn sys_update_ui_title(
             text_node.sections[0].value = "".to_string();
         }
         PlayState::Wave => {
-            text_node.sections[0].value = "Wave in Progress".to_string();
+            text_node.sections[0].value = format!("Wave {} in Progress", wave_number);
         }
         PlayState::Paused => {
             text_node.sections[0].value = "Game Paused".to_string();
@@ -1,102 +1,168 @@
+use std::any::TypeId;
 use std::borrow::Cow;
-use std::{any::TypeId, time::Duration};
 
-use bevy::math::uvec2;
 use bevy::{prelude::*, utils::HashMap};
 use bevy_ecs_tilemap::map::{TilemapGridSize, TilemapType};
-use harvester::{HarvesterPlugin, HarvesterType};
-
+use construction::ConstructionPlugin;
+use definition::{BuildingDefinitionPlugin, BuildingStats};
+use escape::EscapePlugin;
+use harvester::HarvesterPlugin;
+use history::BuildHistoryPlugin;
+use net::NetPlugin;
+use shaker::ShakerPlugin;
+use upgrade::UpgradeTiers;
+use wave_script::{WaveScript, WaveScriptHandle, WaveScriptPlugin};
+
+use crate::fruit_type::FruitSpeciesMap;
+use crate::placement::PlacementRule;
 use crate::ui::CurrentIntention;
 use crate::PlayState;
 use crate::{
-    fruit::{FruitBranch, FruitBranchBundle},
     level::{CurrentLevel, TilePath},
     tree, GameState,
 };
 
 pub trait Building: Send + Sync {
-    fn init_assets(&mut self, asset_server: &AssetServer);
+    /// Stable identifier that building definitions use to pick this behaviour.
+    fn kind(&self) -> &'static str;
+
+    fn stats(&self) -> &BuildingStats;
 
-    fn name(&self) -> Cow<'static, str>;
+    fn stats_mut(&mut self) -> &mut BuildingStats;
 
     fn construct_building(&self, commands: &mut Commands, target: Entity);
 
-    fn sprite_image(&self) -> &Handle<Image>;
+    /// Names of the behaviour specific parameters read by `configure`.
+    fn params(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Reads behaviour specific parameters from a building definition.
+    fn configure(&mut self, _params: &std::collections::HashMap<String, f32>) {}
+
+    fn init_assets(&mut self, asset_server: &AssetServer) {
+        let stats = self.stats_mut();
+        stats.sprite_handle = asset_server.load(stats.sprite.clone());
+    }
+
+    fn name(&self) -> Cow<'static, str> {
+        self.stats().name.clone()
+    }
+
+    fn sprite_image(&self) -> &Handle<Image> {
+        &self.stats().sprite_handle
+    }
+
+    fn tile_size(&self) -> UVec2 {
+        self.stats().tile_size
+    }
+
+    /// Effective range in world units, for buildings that act on an area.
+    fn range(&self) -> Option<usize> {
+        self.stats().range
+    }
+
+    fn placement_rule(&self) -> PlacementRule {
+        self.stats().placement_rule
+    }
 
-    fn tile_size(&self) -> UVec2;
+    /// Funds spent when the building is placed.
+    fn cost(&self) -> usize {
+        self.stats().cost
+    }
 }
 
 #[derive(Resource, Default)]
 pub struct BuildingTypeMap {
     pub type_map: HashMap<TypeId, Box<dyn Building>>,
+    /// Registration order, used to lay out the build menu.
+    pub build_order: Vec<TypeId>,
 }
 
-#[derive(Default)]
-pub struct DebugPlantType {
-    pub sprite_image_handle: Handle<Image>,
+impl BuildingTypeMap {
+    pub fn insert<T: Building + 'static>(&mut self, building_type: T) {
+        let typ = TypeId::of::<T>();
+        if self.type_map.insert(typ, Box::new(building_type)).is_none() {
+            self.build_order.push(typ);
+        }
+    }
+
+    /// Adds a building type that the player can't build themselves.
+    pub fn insert_unlisted<T: Building + 'static>(&mut self, building_type: T) {
+        self.type_map
+            .insert(TypeId::of::<T>(), Box::new(building_type));
+    }
+
+    /// Building types in the order they were registered.
+    pub fn iter(&self) -> impl Iterator<Item = (TypeId, &dyn Building)> {
+        self.build_order
+            .iter()
+            .filter_map(|typ| Some((*typ, self.type_map.get(typ)?.as_ref())))
+    }
 }
 
-impl DebugPlantType {
-    pub const NAME: &'static str = "Debug Roots";
+pub trait BuildingAppExt {
+    /// Adds a building type to the build menu. Its assets are loaded once its
+    /// definition is applied.
+    fn register_building<T: Building + Default + 'static>(&mut self) -> &mut Self;
+
+    /// Adds a building type that is spawned by the game but kept out of the
+    /// build menu.
+    fn register_unlisted_building<T: Building + Default + 'static>(&mut self) -> &mut Self;
 }
 
-impl Building for DebugPlantType {
-    fn init_assets(&mut self, asset_server: &AssetServer) {
-        self.sprite_image_handle = asset_server.load("plant_base_test.png")
+impl BuildingAppExt for App {
+    fn register_building<T: Building + Default + 'static>(&mut self) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(BuildingTypeMap::default)
+            .insert(T::default());
+        self
     }
 
-    fn name(&self) -> Cow<'static, str> {
-        Self::NAME.into()
+    fn register_unlisted_building<T: Building + Default + 'static>(&mut self) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(BuildingTypeMap::default)
+            .insert_unlisted(T::default());
+        self
     }
+}
 
-    fn construct_building(&self, commands: &mut Commands, target: Entity) {
-        commands
-            .entity(target)
-            .insert(tree::Tree::new_bundle(self.sprite_image_handle.clone()))
-            .with_children(|child_commands| {
-                child_commands.spawn(FruitBranchBundle {
-                    branch: FruitBranch { species: 0 },
-                    sprite: SpriteBundle {
-                        ..Default::default()
-                    },
-                });
-            });
-    }
+#[derive(Default)]
+pub struct DebugPlantType {
+    stats: BuildingStats,
+}
 
-    fn sprite_image(&self) -> &Handle<Image> {
-        &self.sprite_image_handle
+impl Building for DebugPlantType {
+    fn kind(&self) -> &'static str {
+        "tree"
     }
 
-    fn tile_size(&self) -> UVec2 {
-        uvec2(1, 1)
+    fn stats(&self) -> &BuildingStats {
+        &self.stats
     }
-}
 
-pub fn sys_setup_building_types(world: &mut World) {
-    let mut building_map = BuildingTypeMap::default();
-    let asset_server = world.get_resource::<AssetServer>().unwrap().clone();
-
-    macro_rules! register_type {
-        ($building_type:ident) => {{
-            let mut building_type = Box::new($building_type::default());
-            building_type.init_assets(&asset_server);
-            building_map
-                .type_map
-                .insert(TypeId::of::<$building_type>(), building_type);
-        }};
+    fn stats_mut(&mut self) -> &mut BuildingStats {
+        &mut self.stats
     }
 
-    register_type!(DebugPlantType);
-    register_type!(HarvesterType);
-
-    world.insert_resource(building_map);
+    fn construct_building(&self, commands: &mut Commands, target: Entity) {
+        tree::Tree::construct(
+            commands,
+            target,
+            self.stats.sprite_handle.clone(),
+            tree::TreeGenome::default(),
+        );
+    }
 }
 
 pub struct BuildingTypePlugin;
 
 impl Plugin for BuildingTypePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, sys_setup_building_types)
+        app.init_resource::<BuildingTypeMap>()
+            // Trees only arrive with waves or from planted seeds; placing them
+            // for free would be an endless source of funds.
+            .register_unlisted_building::<DebugPlantType>()
             .add_systems(
                 Update,
                 (sys_follow_tile_path, sys_show_tower_range).run_if(in_state(GameState::Playing)),
@@ -114,7 +180,15 @@ impl Plugin for BuildingTypePlugin {
                 Update,
                 (sys_intermission_timer).run_if(in_state(PlayState::Intermission)),
             )
-            .add_plugins(HarvesterPlugin);
+            .add_plugins(BuildingDefinitionPlugin)
+            .add_plugins(ConstructionPlugin)
+            .add_plugins(BuildHistoryPlugin)
+            .add_plugins(WaveScriptPlugin)
+            .add_plugins(EscapePlugin)
+            .add_plugins(HarvesterPlugin)
+            .add_plugins(ShakerPlugin)
+            .add_plugins(NetPlugin)
+            .observe(demolish::obs_demolish_building);
     }
 }
 
@@ -164,64 +238,160 @@ pub fn sys_follow_tile_path(
 #[derive(Resource, Default)]
 pub struct NextWaveQueue(pub Vec<Entity>);
 
+/// Trees the player has held back from the next wave.
+#[derive(Resource, Default)]
+pub struct ReserveTrees(pub Vec<Entity>);
+
+/// Spawns a hidden, off-map tree ready to be placed in a wave queue.
+pub fn spawn_queued_tree(
+    commands: &mut Commands,
+    texture: Handle<Image>,
+    genome: tree::TreeGenome,
+) -> Entity {
+    let target = commands
+        .spawn(SpatialBundle {
+            transform: Transform::from_xyz(-10000.0, 0., 0.),
+            visibility: Visibility::Hidden,
+            ..Default::default()
+        })
+        .id();
+    tree::Tree::construct(commands, target, texture, genome);
+    commands.entity(target).insert(Visibility::Hidden);
+    target
+}
+
 #[derive(Resource)]
 pub struct IntermissionTimer(pub Timer);
 
-#[derive(Resource, Default)]
-pub struct CurrentWave {
+/// Trees of one group that are still waiting to enter the path.
+pub struct WaveSpawner {
+    /// Popped from the back.
     pub unit_queue: Vec<Entity>,
+    pub delay_timer: Timer,
     pub next_unit_timer: Timer,
+    pub speed: f32,
 }
 
-impl CurrentWave {
-    pub fn new(unit_spacing_time: Duration) -> Self {
-        CurrentWave {
-            next_unit_timer: Timer::new(unit_spacing_time, TimerMode::Once),
-            ..Default::default()
+impl WaveSpawner {
+    pub fn new(unit_queue: Vec<Entity>, delay_secs: f32, spacing_secs: f32, speed: f32) -> Self {
+        WaveSpawner {
+            unit_queue,
+            delay_timer: Timer::from_seconds(delay_secs, TimerMode::Once),
+            next_unit_timer: Timer::from_seconds(spacing_secs, TimerMode::Once),
+            speed,
         }
     }
 }
 
+#[derive(Resource, Default)]
+pub struct CurrentWave {
+    /// Index of the wave in progress, or of the next one during an
+    /// intermission.
+    pub index: usize,
+    pub spawners: Vec<WaveSpawner>,
+}
+
+impl CurrentWave {
+    pub fn is_spawning(&self) -> bool {
+        self.spawners
+            .iter()
+            .any(|spawner| !spawner.unit_queue.is_empty())
+    }
+}
+
 pub fn sys_intermission_timer(
+    mut commands: Commands,
     time: Res<Time>,
     mut intermission_timer: ResMut<IntermissionTimer>,
     mut next_wave: ResMut<NextWaveQueue>,
     mut current_wave: ResMut<CurrentWave>,
     mut next_play_state: ResMut<NextState<PlayState>>,
+    buildings: Res<BuildingTypeMap>,
+    fruit_map: Res<FruitSpeciesMap>,
+    scripts: Res<Assets<WaveScript>>,
+    script_handle: Option<Res<WaveScriptHandle>>,
 ) {
     intermission_timer.0.tick(time.delta());
     if !intermission_timer.0.finished() {
         return;
     };
 
-    current_wave.unit_queue.extend(next_wave.0.drain(..).rev());
+    let definition = script_handle
+        .and_then(|handle| scripts.get(&handle.0))
+        .and_then(|script| script.wave(current_wave.index))
+        .cloned()
+        .unwrap_or_default();
+    current_wave.spawners.clear();
+    current_wave.spawners.push(WaveSpawner::new(
+        next_wave.0.drain(..).rev().collect(),
+        0.0,
+        definition.queued_spacing_secs,
+        definition.queued_speed,
+    ));
+
+    let Some(tree_type) = buildings.type_map.get(&TypeId::of::<DebugPlantType>()) else {
+        warn!("Tree building type was not found, wave has no new trees");
+        next_play_state.set(PlayState::Wave);
+        return;
+    };
+    let species_count = fruit_map.species_vector.len().max(1);
+    let mut rng = rand::thread_rng();
+    for group in definition.groups.iter() {
+        let species = group.species.as_ref().and_then(|genus| {
+            fruit_map
+                .species_vector
+                .iter()
+                .position(|species| species.genus == *genus)
+        });
+        let unit_queue = (0..group.count)
+            .map(|idx| {
+                let genome =
+                    tree::TreeGenome::wild(&mut rng, species.unwrap_or(idx % species_count));
+                spawn_queued_tree(&mut commands, tree_type.sprite_image().clone(), genome)
+            })
+            .rev()
+            .collect();
+        current_wave.spawners.push(WaveSpawner::new(
+            unit_queue,
+            group.delay_secs,
+            group.spacing_secs,
+            group.speed,
+        ));
+    }
     next_play_state.set(PlayState::Wave);
 }
 
 pub fn sys_entity_leaves_wave(
     mut commands: Commands,
     mut next_wave: ResMut<NextWaveQueue>,
-    leave_query: Query<Entity, With<PathCompleted>>,
+    mut leave_query: Query<(Entity, &mut Transform), With<PathCompleted>>,
 ) {
-    for left in leave_query.iter() {
+    for (left, mut transform) in leave_query.iter_mut() {
         next_wave.0.push(left);
+        // Only the translation is reset, aged trees keep their size.
+        transform.translation = Vec3::new(-10000.0, 0.0, -10000.0);
         commands
             .entity(left)
             .remove::<(PathCompleted, PathFollower)>()
-            .insert((
-                Visibility::Hidden,
-                Transform::from_translation(Vec3::new(-10000.0, 0.0, -10000.0)),
-            ));
+            .insert(Visibility::Hidden);
     }
 }
 
+/// Fired once every tree of a wave has entered and left the path.
+#[derive(Event)]
+pub struct WaveCompletedEvent;
+
 pub fn sys_detect_wave_completed(
-    current_wave: Res<CurrentWave>,
+    mut commands: Commands,
+    mut current_wave: ResMut<CurrentWave>,
     unit_query: Query<Entity, With<PathFollower>>,
     mut next_play_state: ResMut<NextState<PlayState>>,
     mut intermission_timer: ResMut<IntermissionTimer>,
 ) {
-    if current_wave.unit_queue.is_empty() && unit_query.is_empty() {
+    if !current_wave.is_spawning() && unit_query.is_empty() {
+        current_wave.index += 1;
+        current_wave.spawners.clear();
+        commands.trigger(WaveCompletedEvent);
         intermission_timer.0.reset();
         next_play_state.set(PlayState::Intermission);
     }
@@ -231,45 +401,72 @@ pub fn sys_wave_place_entities(
     mut commands: Commands,
     mut wave: ResMut<CurrentWave>,
     time: Res<Time>,
+    trees: Query<&tree::Tree>,
 ) {
-    if wave.unit_queue.is_empty() {
-        return;
-    };
-    wave.next_unit_timer.tick(time.delta());
-    if wave.next_unit_timer.finished() {
-        wave.next_unit_timer.reset();
-        let next_unit = wave.unit_queue.pop().unwrap();
+    for spawner in wave.spawners.iter_mut() {
+        if spawner.unit_queue.is_empty() {
+            continue;
+        }
+        spawner.delay_timer.tick(time.delta());
+        if !spawner.delay_timer.finished() {
+            continue;
+        }
+        spawner.next_unit_timer.tick(time.delta());
+        if !spawner.next_unit_timer.finished() {
+            continue;
+        }
+        spawner.next_unit_timer.reset();
+        let Some(next_unit) = spawner.unit_queue.pop() else {
+            continue;
+        };
+        let speed_multiplier = trees
+            .get(next_unit)
+            .map_or(1.0, tree::Tree::speed_multiplier);
         commands.entity(next_unit).insert((
             Visibility::Inherited,
             PathFollower {
                 current_dist: 0.0,
-                speed: 20.0,
+                speed: spawner.speed * speed_multiplier,
             },
         ));
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct TowerRange(pub usize);
 
+/// Which entry of [`BuildingTypeMap`] a placed building was built from.
+#[derive(Component, Clone, Copy)]
+pub struct BuildingTypeId(pub TypeId);
+
 pub fn sys_show_tower_range(
     intention: Res<CurrentIntention>,
-    query: Query<(&GlobalTransform, &TowerRange)>,
+    query: Query<(&GlobalTransform, &TowerRange, Option<&UpgradeTiers>)>,
     mut gizmos: Gizmos,
 ) {
     let CurrentIntention::Command(_, ent) = *intention else {
         return;
     };
 
-    let Ok((gt, range)) = query.get(ent) else {
+    let Ok((gt, range, tiers)) = query.get(ent) else {
         return;
     };
 
-    gizmos.circle_2d(
-        gt.translation().xy(),
-        range.0 as f32,
-        bevy::color::palettes::css::BLUE,
+    let color = tiers.map_or(
+        Color::from(bevy::color::palettes::css::BLUE),
+        UpgradeTiers::tier_color,
     );
+    gizmos.circle_2d(gt.translation().xy(), range.0 as f32, color);
 }
 
+pub mod construction;
+pub mod definition;
+pub mod demolish;
+pub mod depot;
+pub mod escape;
 pub mod harvester;
+pub mod history;
+pub mod net;
+pub mod shaker;
+pub mod upgrade;
+pub mod wave_script;
@@ -0,0 +1,112 @@
+use std::any::TypeId;
+
+use bevy::prelude::*;
+
+use crate::{placement::Footprint, GameState, MapQuery};
+
+use super::{Building, BuildingTypeMap};
+
+/// A placed building that is still being built. It has none of its
+/// behaviour components until construction finishes.
+#[derive(Component)]
+pub struct UnderConstruction {
+    pub building_type: TypeId,
+    pub timer: Timer,
+}
+
+/// Translucent stand-in sprite shown while a building is under construction.
+#[derive(Component)]
+pub struct Scaffold;
+
+const PROGRESS_BAR_HEIGHT_OFFSET: f32 = 4.0;
+
+/// Starts building `building_type` on `target`, or finishes it straight away
+/// if it has no build time. `size` is the world size of its footprint.
+pub fn begin_construction(
+    commands: &mut Commands,
+    target: Entity,
+    type_id: TypeId,
+    building_type: &dyn Building,
+    size: Vec2,
+) {
+    let build_secs = building_type.stats().build_secs;
+    if build_secs <= 0.0 {
+        building_type.construct_building(commands, target);
+        return;
+    }
+    commands
+        .entity(target)
+        .insert(UnderConstruction {
+            building_type: type_id,
+            timer: Timer::from_seconds(build_secs, TimerMode::Once),
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                Scaffold,
+                SpriteBundle {
+                    texture: building_type.sprite_image().clone(),
+                    sprite: Sprite {
+                        color: Color::srgba(0.6, 0.6, 0.6, 0.5),
+                        custom_size: Some(size),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            ));
+        });
+}
+
+pub fn sys_advance_construction(
+    mut commands: Commands,
+    time: Res<Time>,
+    building_types: Res<BuildingTypeMap>,
+    mut sites: Query<(Entity, &mut UnderConstruction, &Children)>,
+    scaffolds: Query<(), With<Scaffold>>,
+) {
+    for (site_ent, mut construction, children) in sites.iter_mut() {
+        construction.timer.tick(time.delta());
+        if !construction.timer.finished() {
+            continue;
+        }
+        for child in children.iter().filter(|child| scaffolds.contains(**child)) {
+            commands.entity(*child).despawn_recursive();
+        }
+        commands.entity(site_ent).remove::<UnderConstruction>();
+        let Some(building_type) = building_types.type_map.get(&construction.building_type) else {
+            warn!("Building type under construction was not found");
+            continue;
+        };
+        building_type.construct_building(&mut commands, site_ent);
+    }
+}
+
+pub fn sys_show_construction_progress(
+    sites: Query<(&GlobalTransform, &UnderConstruction, &Footprint)>,
+    map_query: MapQuery,
+    mut gizmos: Gizmos,
+) {
+    let Ok((_, _, grid_size, _)) = map_query.get_single() else {
+        return;
+    };
+    for (gt, construction, footprint) in sites.iter() {
+        let size = footprint.size.as_vec2() * Vec2::new(grid_size.x, grid_size.y);
+        let left = gt.translation().xy()
+            + Vec2::new(-size.x / 2.0, size.y / 2.0 + PROGRESS_BAR_HEIGHT_OFFSET);
+        let right = left + Vec2::X * size.x;
+        let progress = left.lerp(right, construction.timer.fraction());
+        gizmos.line_2d(left, right, bevy::color::palettes::css::DIM_GRAY);
+        gizmos.line_2d(left, progress, bevy::color::palettes::css::LIME);
+    }
+}
+
+pub struct ConstructionPlugin;
+
+impl Plugin for ConstructionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (sys_advance_construction, sys_show_construction_progress)
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    math::uvec2,
+    prelude::*,
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::placement::PlacementRule;
+
+use super::BuildingTypeMap;
+
+/// Stats shared by every building type. They are all read from the
+/// definitions file; until it loads they are zeroed.
+pub struct BuildingStats {
+    pub name: Cow<'static, str>,
+    /// Asset path of the sprite.
+    pub sprite: String,
+    pub sprite_handle: Handle<Image>,
+    pub tile_size: UVec2,
+    pub cost: usize,
+    pub range: Option<usize>,
+    pub placement_rule: PlacementRule,
+    /// Seconds spent under construction before the building starts working.
+    pub build_secs: f32,
+}
+
+impl Default for BuildingStats {
+    fn default() -> Self {
+        BuildingStats {
+            name: "".into(),
+            sprite: String::new(),
+            sprite_handle: Handle::default(),
+            tile_size: uvec2(1, 1),
+            cost: 0,
+            range: None,
+            placement_rule: PlacementRule::OffPath,
+            build_secs: 0.0,
+        }
+    }
+}
+
+impl BuildingStats {
+    pub fn apply(&mut self, definition: &BuildingDefinition) {
+        self.name = definition.name.clone().into();
+        self.sprite = definition.sprite.clone();
+        self.tile_size = uvec2(definition.footprint.0, definition.footprint.1);
+        self.cost = definition.cost;
+        self.range = definition.range;
+        self.placement_rule = definition.placement;
+        self.build_secs = definition.build_secs;
+    }
+}
+
+/// One building as described in a `.buildings.ron` file.
+#[derive(Deserialize, Debug)]
+pub struct BuildingDefinition {
+    /// Which registered behaviour the definition configures, see
+    /// [`super::Building::kind`].
+    pub kind: String,
+    pub name: String,
+    pub sprite: String,
+    pub footprint: (u32, u32),
+    #[serde(default)]
+    pub cost: usize,
+    #[serde(default)]
+    pub range: Option<usize>,
+    #[serde(default)]
+    pub placement: PlacementRule,
+    #[serde(default)]
+    pub build_secs: f32,
+    /// Behaviour specific tuning values, such as a harvester's cooldown.
+    #[serde(default)]
+    pub params: HashMap<String, f32>,
+}
+
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct BuildingDefinitions(pub Vec<BuildingDefinition>);
+
+impl BuildingDefinitions {
+    /// Rejects definitions the registered building behaviours can't work
+    /// with. `kinds` maps each registered kind to the parameters it requires.
+    pub fn validate(
+        &self,
+        kinds: &HashMap<&'static str, &'static [&'static str]>,
+    ) -> Result<(), BuildingDefinitionsLoaderError> {
+        for definition in self.0.iter() {
+            let kind = || definition.kind.clone();
+            let Some(required) = kinds.get(definition.kind.as_str()) else {
+                return Err(BuildingDefinitionsLoaderError::UnknownKind(kind()));
+            };
+            if definition.footprint.0 == 0 || definition.footprint.1 == 0 {
+                return Err(BuildingDefinitionsLoaderError::ZeroFootprint(kind()));
+            }
+            if let Some(param) = definition
+                .params
+                .keys()
+                .find(|param| !required.contains(&param.as_str()))
+            {
+                return Err(BuildingDefinitionsLoaderError::UnknownParam {
+                    kind: kind(),
+                    param: param.clone(),
+                });
+            }
+            for param in required.iter() {
+                match definition.params.get(*param) {
+                    None => {
+                        return Err(BuildingDefinitionsLoaderError::MissingParam {
+                            kind: kind(),
+                            param: param.to_string(),
+                        })
+                    }
+                    // Every parameter is a rate, count or size.
+                    Some(value) if *value <= 0.0 => {
+                        return Err(BuildingDefinitionsLoaderError::NonPositiveParam {
+                            kind: kind(),
+                            param: param.to_string(),
+                        })
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        if let Some(kind) = kinds
+            .keys()
+            .find(|kind| !self.0.iter().any(|definition| definition.kind == **kind))
+        {
+            return Err(BuildingDefinitionsLoaderError::MissingKind(
+                kind.to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub struct BuildingDefinitionsLoader {
+    kinds: HashMap<&'static str, &'static [&'static str]>,
+}
+
+impl FromWorld for BuildingDefinitionsLoader {
+    fn from_world(world: &mut World) -> Self {
+        let kinds = world
+            .get_resource::<BuildingTypeMap>()
+            .map(|building_map| {
+                building_map
+                    .type_map
+                    .values()
+                    .map(|building_type| (building_type.kind(), building_type.params()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        BuildingDefinitionsLoader { kinds }
+    }
+}
+
+/// Set once the definitions have been copied onto the building types.
+#[derive(Resource)]
+pub struct BuildingDefinitionsApplied;
+
+#[derive(Error, Debug)]
+pub enum BuildingDefinitionsLoaderError {
+    #[error("Could not read the building definitions: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse the building definitions: {0}")]
+    Ron(#[from] ron::de::SpannedError),
+    #[error("No building is registered for kind {0:?}")]
+    UnknownKind(String),
+    #[error("Building {0:?} has no definition")]
+    MissingKind(String),
+    #[error("Building {0:?} has an empty footprint")]
+    ZeroFootprint(String),
+    #[error("Building {kind:?} has no {param:?} parameter")]
+    UnknownParam { kind: String, param: String },
+    #[error("Building {kind:?} is missing its {param:?} parameter")]
+    MissingParam { kind: String, param: String },
+    #[error("Building {kind:?} needs a positive {param:?} parameter")]
+    NonPositiveParam { kind: String, param: String },
+}
+
+impl AssetLoader for BuildingDefinitionsLoader {
+    type Asset = BuildingDefinitions;
+    type Settings = ();
+    type Error = BuildingDefinitionsLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let definitions: BuildingDefinitions = ron::de::from_bytes(&bytes)?;
+        definitions.validate(&self.kinds)?;
+        Ok(definitions)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["buildings.ron"]
+    }
+}
+
+#[derive(Resource)]
+pub struct BuildingDefinitionsHandle(pub Handle<BuildingDefinitions>);
+
+pub fn sys_load_building_definitions(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(BuildingDefinitionsHandle(
+        asset_server.load("units.buildings.ron"),
+    ));
+}
+
+/// Copies loaded (or hot reloaded) definitions onto the registered building
+/// types. Buildings that are already placed keep their stats.
+pub fn sys_apply_building_definitions(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<BuildingDefinitions>>,
+    handle: Res<BuildingDefinitionsHandle>,
+    definitions: Res<Assets<BuildingDefinitions>>,
+    asset_server: Res<AssetServer>,
+    mut building_map: ResMut<BuildingTypeMap>,
+) {
+    for event in events.read() {
+        let (AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }) = *event
+        else {
+            continue;
+        };
+        if id != handle.0.id() {
+            continue;
+        }
+        let Some(BuildingDefinitions(definitions)) = definitions.get(id) else {
+            continue;
+        };
+        for definition in definitions.iter() {
+            let Some(building_type) = building_map
+                .type_map
+                .values_mut()
+                .find(|building_type| building_type.kind() == definition.kind)
+            else {
+                continue;
+            };
+            building_type.stats_mut().apply(definition);
+            building_type.configure(&definition.params);
+            building_type.init_assets(&asset_server);
+        }
+        commands.insert_resource(BuildingDefinitionsApplied);
+    }
+}
+
+pub struct BuildingDefinitionPlugin;
+
+impl Plugin for BuildingDefinitionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<BuildingDefinitions>()
+            .add_systems(Startup, sys_load_building_definitions)
+            .add_systems(
+                Update,
+                sys_apply_building_definitions.run_if(resource_exists::<BuildingDefinitionsHandle>),
+            );
+    }
+
+    // The loader checks definitions against every registered building, so it
+    // is only created once all plugins have registered theirs.
+    fn finish(&self, app: &mut App) {
+        app.init_asset_loader::<BuildingDefinitionsLoader>();
+    }
+}
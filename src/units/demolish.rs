@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+
+use super::history::{record_action, BuildAction, BuildingSnapshot};
+
+use crate::{
+    placement::{Footprint, TileOccupancy},
+    ui::CurrentIntention,
+    Funds,
+};
+
+/// Fraction of everything spent on a building that is paid back on demolition.
+pub const REFUND_FRACTION: f32 = 0.5;
+
+/// Funds spent on a building so far, including its upgrades.
+#[derive(Component, Default)]
+pub struct Investment(pub usize);
+
+impl Investment {
+    pub fn refund(&self) -> usize {
+        (self.0 as f32 * REFUND_FRACTION).floor() as usize
+    }
+}
+
+#[derive(Event)]
+pub struct DemolishBuildingEvent;
+
+pub fn obs_demolish_building(
+    event: Trigger<DemolishBuildingEvent>,
+    mut commands: Commands,
+    mut funds: ResMut<Funds>,
+    mut occupancy: ResMut<TileOccupancy>,
+    current: Res<CurrentIntention>,
+    buildings: Query<(Option<&Footprint>, Option<&Investment>)>,
+) {
+    let building_ent = event.entity();
+    let Ok((footprint, investment)) = buildings.get(building_ent) else {
+        return;
+    };
+    if let Some(footprint) = footprint {
+        occupancy.release(footprint);
+    }
+    let refund = investment.map_or(0, Investment::refund);
+    funds.0 += refund;
+    commands.add(move |world: &mut World| {
+        if let Some(snapshot) = BuildingSnapshot::capture(world, building_ent) {
+            record_action(
+                world,
+                BuildAction::Demolish {
+                    entity: building_ent,
+                    snapshot,
+                    refund,
+                },
+            );
+        }
+    });
+    commands.entity(building_ent).despawn_recursive();
+
+    if let CurrentIntention::Inspect(_, ent) | CurrentIntention::Command(_, ent) = *current {
+        if ent == building_ent {
+            commands.insert_resource(CurrentIntention::None);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::any::TypeId;
+
+use bevy::prelude::*;
+use bevy_mod_picking::{events::Pointer, prelude::On, selection::Select, PickableBundle};
+
+use crate::{ui::CurrentIntention, units::Building};
+
+use super::definition::BuildingStats;
+
+/// Drop-off point where harvester workers turn their load into score.
+#[derive(Component)]
+pub struct Depot;
+
+#[derive(Default)]
+pub struct DepotType {
+    stats: BuildingStats,
+}
+
+impl Building for DepotType {
+    fn kind(&self) -> &'static str {
+        "depot"
+    }
+
+    fn stats(&self) -> &BuildingStats {
+        &self.stats
+    }
+
+    fn stats_mut(&mut self) -> &mut BuildingStats {
+        &mut self.stats
+    }
+
+    fn construct_building(&self, commands: &mut Commands, target: Entity) {
+        commands.entity(target).insert((
+            Depot,
+            self.stats.sprite_handle.clone(),
+            Sprite {
+                ..Default::default()
+            },
+            PickableBundle::default(),
+            On::<Pointer<Select>>::commands_mut(|event, commands| {
+                commands.insert_resource(CurrentIntention::Command(
+                    TypeId::of::<DepotType>(),
+                    event.target,
+                ));
+            }),
+        ));
+    }
+}
@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use super::{
+    wave_script::{WaveScript, WaveScriptHandle},
+    PathCompleted,
+};
+
+use crate::{
+    fruit::{Fruit, FruitBranchAttachment, FruitGrowthState},
+    tree::Tree,
+    GameState, PlayState,
+};
+
+/// What the level charges for trees that leave the path with ripe fruit.
+/// Fields missing from a level file keep the values of [`EscapeRules::default`].
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct EscapeRules {
+    pub lives: usize,
+    /// Lives lost for every tree that escapes with ripe fruit.
+    pub lives_per_tree: usize,
+    /// Lives lost for every ripe fruit carried off the map.
+    pub lives_per_fruit: usize,
+}
+
+impl Default for EscapeRules {
+    fn default() -> Self {
+        EscapeRules {
+            lives: 20,
+            lives_per_tree: 1,
+            lives_per_fruit: 0,
+        }
+    }
+}
+
+impl EscapeRules {
+    pub fn penalty(&self, wasted_fruit: usize) -> usize {
+        self.lives_per_tree + self.lives_per_fruit * wasted_fruit
+    }
+}
+
+/// Lives left before the game is lost.
+#[derive(Resource, Default)]
+pub struct Lives(pub usize);
+
+/// Running totals shown in the game over summary.
+#[derive(Resource, Default)]
+pub struct EscapeStats {
+    pub escaped_trees: usize,
+    pub wasted_fruit: usize,
+}
+
+fn current_rules(
+    scripts: &Assets<WaveScript>,
+    script_handle: Option<&WaveScriptHandle>,
+) -> EscapeRules {
+    script_handle
+        .and_then(|handle| scripts.get(&handle.0))
+        .map(|script| script.escape.clone())
+        .unwrap_or_default()
+}
+
+pub fn sys_reset_lives(
+    mut commands: Commands,
+    scripts: Res<Assets<WaveScript>>,
+    script_handle: Option<Res<WaveScriptHandle>>,
+) {
+    let rules = current_rules(&scripts, script_handle.as_deref());
+    commands.insert_resource(Lives(rules.lives));
+    commands.insert_resource(EscapeStats::default());
+}
+
+/// Charges lives for the ripe fruit on trees that reached the end of the path.
+/// That fruit is wasted and dropped from the tree.
+pub fn sys_penalize_escaped_trees(
+    mut commands: Commands,
+    mut lives: ResMut<Lives>,
+    mut stats: ResMut<EscapeStats>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    scripts: Res<Assets<WaveScript>>,
+    script_handle: Option<Res<WaveScriptHandle>>,
+    escaped: Query<&Children, (With<Tree>, With<PathCompleted>)>,
+    branches: Query<&FruitBranchAttachment>,
+    fruits: Query<&FruitGrowthState, With<Fruit>>,
+) {
+    let rules = current_rules(&scripts, script_handle.as_deref());
+    for children in escaped.iter() {
+        let mut wasted_fruit = 0;
+        for branch_ent in children.iter() {
+            let Ok(FruitBranchAttachment::Fruit(fruit_ent)) = branches.get(*branch_ent) else {
+                continue;
+            };
+            let Ok(FruitGrowthState::Fruited { .. }) = fruits.get(*fruit_ent) else {
+                continue;
+            };
+            wasted_fruit += 1;
+            commands.entity(*fruit_ent).despawn_recursive();
+            commands
+                .entity(*branch_ent)
+                .remove::<FruitBranchAttachment>();
+        }
+        if wasted_fruit == 0 {
+            continue;
+        }
+        stats.escaped_trees += 1;
+        stats.wasted_fruit += wasted_fruit;
+        lives.0 = lives.0.saturating_sub(rules.penalty(wasted_fruit));
+    }
+    if lives.0 == 0 && !escaped.is_empty() {
+        info!("Out of lives, game over");
+        next_game_state.set(GameState::GameOver);
+    }
+}
+
+pub struct EscapePlugin;
+
+impl Plugin for EscapePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Lives>()
+            .init_resource::<EscapeStats>()
+            .add_systems(OnEnter(PlayState::Setup), sys_reset_lives)
+            .add_systems(
+                Update,
+                sys_penalize_escaped_trees
+                    .before(super::sys_entity_leaves_wave)
+                    .run_if(in_state(PlayState::Wave)),
+            );
+    }
+}
@@ -1,40 +1,144 @@
-use std::any::TypeId;
+use std::{any::TypeId, collections::HashMap, time::Duration};
 
-use bevy::{math::uvec2, prelude::*};
+use bevy::{prelude::*, utils::HashSet};
 use bevy_mod_picking::{events::Pointer, prelude::On, selection::Select, PickableBundle};
 use bevy_spatial::{kdtree::KDTree2, SpatialAccess};
 
 use crate::{
-    fruit::{FruitGrowthState, HarvestFruitEvent},
+    fruit::{
+        Fruit, FruitBranch, FruitFlight, FruitGrowthState, HarvestFruitEvent, HarvestReservation,
+    },
+    fruit_type::FruitSpeciesMap,
+    tree::{SeedBank, TreeGenome},
     ui::CurrentIntention,
     units::Building,
-    GameState, SpatialTracked,
+    Funds, GameState, Score, SpatialTracked,
 };
 
-use super::TowerRange;
+use super::{
+    definition::BuildingStats,
+    depot::{Depot, DepotType},
+    upgrade::{obs_upgrade_harvester, UpgradeTiers},
+    BuildingAppExt, PathFollower, TowerRange,
+};
+
+/// Shortest time between picks, so a harvester never picks every frame.
+pub const MIN_COOLDOWN_SECS: f32 = 0.05;
+
+#[derive(Component, Clone)]
+pub struct Harvester {
+    /// Seconds between picks.
+    pub cooldown_secs: f32,
+    /// Maximum number of fruit collected by a single pick.
+    pub targets_per_pick: usize,
+    pub cooldown: Timer,
+}
+
+impl Harvester {
+    pub fn new(cooldown_secs: f32, targets_per_pick: usize) -> Self {
+        let cooldown_secs = cooldown_secs.max(MIN_COOLDOWN_SECS);
+        let mut cooldown = Timer::from_seconds(cooldown_secs, TimerMode::Once);
+        // Newly built harvesters are ready to pick straight away.
+        cooldown.set_elapsed(cooldown.duration());
+        Harvester {
+            cooldown_secs,
+            targets_per_pick,
+            cooldown,
+        }
+    }
+
+    pub fn set_cooldown_secs(&mut self, cooldown_secs: f32) {
+        self.cooldown_secs = cooldown_secs.max(MIN_COOLDOWN_SECS);
+        self.cooldown
+            .set_duration(Duration::from_secs_f32(self.cooldown_secs));
+    }
+
+    /// Fruit per second this harvester can collect at most. The cooldown is
+    /// never below [`MIN_COOLDOWN_SECS`], so this stays finite.
+    pub fn harvest_rate(&self) -> f32 {
+        self.targets_per_pick as f32 / self.cooldown_secs
+    }
+}
 
-#[derive(Component)]
-pub struct Harvester;
+/// What a harvester has actually collected so far.
+#[derive(Component, Default)]
+pub struct HarvestStats {
+    pub fruit_collected: usize,
+    pub value_collected: usize,
+}
+
+/// How a harvester chooses between ripe fruit in range.
+#[derive(Component, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TargetingPolicy {
+    #[default]
+    Nearest,
+    Ripest,
+    MostValuable,
+    FirstAlongPath,
+    LastAlongPath,
+}
+
+impl TargetingPolicy {
+    pub const ALL: [TargetingPolicy; 5] = [
+        TargetingPolicy::Nearest,
+        TargetingPolicy::Ripest,
+        TargetingPolicy::MostValuable,
+        TargetingPolicy::FirstAlongPath,
+        TargetingPolicy::LastAlongPath,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TargetingPolicy::Nearest => "Nearest",
+            TargetingPolicy::Ripest => "Ripest",
+            TargetingPolicy::MostValuable => "Most valuable",
+            TargetingPolicy::FirstAlongPath => "First along path",
+            TargetingPolicy::LastAlongPath => "Last along path",
+        }
+    }
+}
 
 #[derive(Default)]
 pub struct HarvesterType {
-    sprite_handle: Handle<Image>,
+    stats: BuildingStats,
+    cooldown_secs: f32,
+    targets_per_pick: usize,
 }
 
 impl Building for HarvesterType {
-    fn init_assets(&mut self, asset_server: &AssetServer) {
-        self.sprite_handle = asset_server.load("harvester_test.png");
+    fn kind(&self) -> &'static str {
+        "harvester"
+    }
+
+    fn stats(&self) -> &BuildingStats {
+        &self.stats
+    }
+
+    fn stats_mut(&mut self) -> &mut BuildingStats {
+        &mut self.stats
     }
 
-    fn name(&self) -> std::borrow::Cow<'static, str> {
-        "Harvester".into()
+    fn params(&self) -> &'static [&'static str] {
+        &["cooldown_secs", "targets_per_pick"]
+    }
+
+    fn configure(&mut self, params: &HashMap<String, f32>) {
+        if let Some(cooldown_secs) = params.get("cooldown_secs") {
+            self.cooldown_secs = *cooldown_secs;
+        }
+        if let Some(targets_per_pick) = params.get("targets_per_pick") {
+            self.targets_per_pick = *targets_per_pick as usize;
+        }
     }
 
     fn construct_building(&self, commands: &mut Commands, target: Entity) {
         commands.entity(target).insert((
-            Harvester,
-            TowerRange(50),
-            self.sprite_handle.clone(),
+            Harvester::new(self.cooldown_secs, self.targets_per_pick),
+            TowerRange(self.stats.range.unwrap_or_default()),
+            TargetingPolicy::default(),
+            HarvestStats::default(),
+            UpgradeTiers::default(),
+            self.stats.sprite_handle.clone(),
             Sprite {
                 ..Default::default()
             },
@@ -47,31 +151,335 @@ impl Building for HarvesterType {
             }),
         ));
     }
+}
 
-    fn sprite_image(&self) -> &Handle<Image> {
-        &self.sprite_handle
+pub fn sys_harvester_look_for_fruit(
+    mut commands: Commands,
+    time: Res<Time>,
+    spatial_tree: Res<KDTree2<SpatialTracked>>,
+    mut harvesters: Query<(
+        Entity,
+        &mut Harvester,
+        &GlobalTransform,
+        &TowerRange,
+        &TargetingPolicy,
+    )>,
+    fruit: Query<(
+        &Fruit,
+        &FruitGrowthState,
+        &Parent,
+        Option<&HarvestReservation>,
+    )>,
+    branches: Query<&Parent, With<FruitBranch>>,
+    path_followers: Query<&PathFollower>,
+    fruit_map: Res<FruitSpeciesMap>,
+) {
+    // Reservations made by this system are only applied once its commands
+    // run, so fruit claimed earlier this frame are tracked here as well.
+    let mut claimed = HashSet::new();
+    for (harvester_ent, mut harvester, transform, range, policy) in harvesters.iter_mut() {
+        harvester.cooldown.tick(time.delta());
+        if !harvester.cooldown.finished() {
+            continue;
+        }
+        let origin = transform.translation().xy();
+        // Pair each ripe fruit with a priority; higher priorities are picked first.
+        let mut candidates: Vec<(Entity, f32)> = spatial_tree
+            .within_distance(origin, range.0 as f32)
+            .into_iter()
+            .filter_map(|(pos, entity)| {
+                let entity = entity?;
+                let Ok((
+                    fruit,
+                    FruitGrowthState::Fruited { seconds_ripe },
+                    branch_ent,
+                    reservation,
+                )) = fruit.get(entity)
+                else {
+                    return None;
+                };
+                if claimed.contains(&entity)
+                    || reservation.is_some_and(|r| r.harvester_ent != harvester_ent)
+                {
+                    return None;
+                }
+                let path_dist = || {
+                    branches
+                        .get(branch_ent.get())
+                        .and_then(|tree_ent| path_followers.get(tree_ent.get()))
+                        .map_or(0.0, |follower| follower.current_dist)
+                };
+                let priority = match policy {
+                    TargetingPolicy::Nearest => -pos.distance(origin),
+                    TargetingPolicy::Ripest => *seconds_ripe,
+                    TargetingPolicy::MostValuable => fruit_map
+                        .species_vector
+                        .get(fruit.fruit_type)
+                        .map_or(0.0, |species| species.value as f32),
+                    TargetingPolicy::FirstAlongPath => path_dist(),
+                    TargetingPolicy::LastAlongPath => -path_dist(),
+                };
+                Some((entity, priority))
+            })
+            .collect();
+        if candidates.is_empty() {
+            continue;
+        }
+        candidates.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        let targets: Vec<Entity> = candidates
+            .into_iter()
+            .take(harvester.targets_per_pick)
+            .map(|(entity, _)| entity)
+            .collect();
+        harvester.cooldown.reset();
+        for target in targets.iter() {
+            claimed.insert(*target);
+            commands
+                .entity(*target)
+                .insert(HarvestReservation { harvester_ent });
+        }
+        commands.trigger_targets(HarvestFruitEvent { harvester_ent }, targets);
     }
+}
+
+/// A harvester that walks to ripe fruit and carries it back to a depot.
+#[derive(Component, Clone)]
+pub struct HarvesterWorker {
+    /// Movement speed in world units per second.
+    pub speed: f32,
+    /// Number of fruit the worker can carry before returning to a depot.
+    pub capacity: usize,
+}
 
-    fn tile_size(&self) -> UVec2 {
-        uvec2(1, 1)
+/// Fruit picked by a worker and not yet delivered to a depot.
+#[derive(Component, Default)]
+pub struct HarvestLoad {
+    pub fruit_count: usize,
+    /// Score value of the fruit that has reached the worker so far.
+    pub value: usize,
+    pub seeds: Vec<TreeGenome>,
+}
+
+impl HarvestLoad {
+    pub fn is_empty(&self) -> bool {
+        self.fruit_count == 0 && self.value == 0 && self.seeds.is_empty()
     }
 }
 
-pub fn sys_harvester_look_for_fruit(
+#[derive(Component, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WorkerTask {
+    #[default]
+    Idle,
+    Collect(Entity),
+    Deliver(Entity),
+}
+
+/// Distance at which a worker can pick fruit or unload at a depot.
+pub const WORKER_REACH: f32 = 6.0;
+
+#[derive(Default)]
+pub struct HarvesterWorkerType {
+    stats: BuildingStats,
+    speed: f32,
+    capacity: usize,
+}
+
+impl Building for HarvesterWorkerType {
+    fn kind(&self) -> &'static str {
+        "harvester_worker"
+    }
+
+    fn stats(&self) -> &BuildingStats {
+        &self.stats
+    }
+
+    fn stats_mut(&mut self) -> &mut BuildingStats {
+        &mut self.stats
+    }
+
+    fn params(&self) -> &'static [&'static str] {
+        &["speed", "capacity"]
+    }
+
+    fn configure(&mut self, params: &HashMap<String, f32>) {
+        if let Some(speed) = params.get("speed") {
+            self.speed = *speed;
+        }
+        if let Some(capacity) = params.get("capacity") {
+            self.capacity = *capacity as usize;
+        }
+    }
+
+    fn construct_building(&self, commands: &mut Commands, target: Entity) {
+        commands.entity(target).insert((
+            HarvesterWorker {
+                speed: self.speed,
+                capacity: self.capacity,
+            },
+            HarvestLoad::default(),
+            HarvestStats::default(),
+            UpgradeTiers::default(),
+            WorkerTask::default(),
+            TowerRange(self.stats.range.unwrap_or_default()),
+            self.stats.sprite_handle.clone(),
+            Sprite {
+                ..Default::default()
+            },
+            PickableBundle::default(),
+            On::<Pointer<Select>>::commands_mut(|event, commands| {
+                commands.insert_resource(CurrentIntention::Command(
+                    TypeId::of::<HarvesterWorkerType>(),
+                    event.target,
+                ));
+            }),
+        ));
+    }
+}
+
+pub fn sys_harvester_target_set(
     mut commands: Commands,
     spatial_tree: Res<KDTree2<SpatialTracked>>,
-    harvesters: Query<(Entity, &Harvester, &GlobalTransform, &TowerRange)>,
-    fruit: Query<&FruitGrowthState>,
+    mut workers: Query<(
+        Entity,
+        &HarvesterWorker,
+        &HarvestLoad,
+        &GlobalTransform,
+        &TowerRange,
+        &mut WorkerTask,
+    )>,
+    fruit: Query<(&FruitGrowthState, Option<&HarvestReservation>)>,
+    depots: Query<(Entity, &GlobalTransform), With<Depot>>,
+    flights: Query<&FruitFlight>,
 ) {
-    for (harvester_ent, _harvester, transform, range) in harvesters.iter() {
-        for (_, entity) in
-            spatial_tree.within_distance(transform.translation().xy(), range.0 as f32)
-        {
-            let Some(entity) = entity else { continue };
-            let Ok(FruitGrowthState::Fruited) = fruit.get(entity) else {
+    let mut claimed = HashSet::new();
+    for (worker_ent, worker, load, transform, range, mut task) in workers.iter_mut() {
+        // Drop targets whose reservation was lost or that were already picked.
+        if let WorkerTask::Collect(fruit_ent) = *task {
+            let still_ours = matches!(
+                fruit.get(fruit_ent),
+                Ok((FruitGrowthState::Fruited { .. }, Some(reservation)))
+                    if reservation.harvester_ent == worker_ent
+            );
+            if !still_ours {
+                *task = WorkerTask::Idle;
+            }
+        }
+        if *task != WorkerTask::Idle {
+            continue;
+        }
+        let origin = transform.translation().xy();
+        // Fruit still flying to the worker already takes up room.
+        let in_flight = flights
+            .iter()
+            .filter(|flight| flight.harvester_ent == worker_ent)
+            .count();
+        if load.fruit_count + in_flight < worker.capacity {
+            let nearest_fruit = spatial_tree
+                .within_distance(origin, range.0 as f32)
+                .into_iter()
+                .filter_map(|(pos, entity)| Some((pos, entity?)))
+                .filter(|(_, entity)| {
+                    !claimed.contains(entity)
+                        && matches!(
+                            fruit.get(*entity),
+                            Ok((FruitGrowthState::Fruited { .. }, None))
+                        )
+                })
+                .min_by(|(a, _), (b, _)| a.distance(origin).total_cmp(&b.distance(origin)));
+            if let Some((_, fruit_ent)) = nearest_fruit {
+                claimed.insert(fruit_ent);
+                commands.entity(fruit_ent).insert(HarvestReservation {
+                    harvester_ent: worker_ent,
+                });
+                *task = WorkerTask::Collect(fruit_ent);
                 continue;
-            };
-            commands.trigger_targets(HarvestFruitEvent { harvester_ent }, entity);
+            }
+        }
+        if load.is_empty() {
+            continue;
+        }
+        let nearest_depot = depots.iter().min_by(|(_, a), (_, b)| {
+            let dist_a = a.translation().xy().distance(origin);
+            let dist_b = b.translation().xy().distance(origin);
+            dist_a.total_cmp(&dist_b)
+        });
+        if let Some((depot_ent, _)) = nearest_depot {
+            *task = WorkerTask::Deliver(depot_ent);
+        }
+    }
+}
+
+pub fn sys_harvester_move_to_target(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut score: ResMut<Score>,
+    mut funds: ResMut<Funds>,
+    mut seed_bank: ResMut<SeedBank>,
+    mut workers: Query<(
+        Entity,
+        &HarvesterWorker,
+        &mut HarvestLoad,
+        &mut Transform,
+        &mut WorkerTask,
+    )>,
+    targets: Query<&GlobalTransform>,
+) {
+    for (worker_ent, worker, mut load, mut transform, mut task) in workers.iter_mut() {
+        let (WorkerTask::Collect(target_ent) | WorkerTask::Deliver(target_ent)) = *task else {
+            continue;
+        };
+        let Ok(target_gt) = targets.get(target_ent) else {
+            *task = WorkerTask::Idle;
+            continue;
+        };
+        let to_target = target_gt.translation().xy() - transform.translation.xy();
+        let step = worker.speed * time.delta_seconds();
+        if to_target.length() > WORKER_REACH {
+            let movement = to_target.clamp_length_max(step);
+            transform.translation += movement.extend(0.0);
+            continue;
+        }
+        match *task {
+            WorkerTask::Collect(fruit_ent) => {
+                commands.trigger_targets(
+                    HarvestFruitEvent {
+                        harvester_ent: worker_ent,
+                    },
+                    fruit_ent,
+                );
+            }
+            WorkerTask::Deliver(_) => {
+                score.0 += load.value;
+                funds.0 += load.value;
+                seed_bank.0.append(&mut load.seeds);
+                *load = HarvestLoad::default();
+            }
+            WorkerTask::Idle => (),
+        }
+        *task = WorkerTask::Idle;
+    }
+}
+
+/// Releases reservations held by harvesters that are gone or whose fruit
+/// has moved out of their range.
+pub fn sys_release_harvest_reservations(
+    mut commands: Commands,
+    fruit: Query<(Entity, &HarvestReservation, &GlobalTransform)>,
+    harvesters: Query<(&GlobalTransform, &TowerRange)>,
+) {
+    for (fruit_ent, reservation, fruit_gt) in fruit.iter() {
+        let in_range =
+            harvesters
+                .get(reservation.harvester_ent)
+                .is_ok_and(|(harvester_gt, range)| {
+                    harvester_gt
+                        .translation()
+                        .xy()
+                        .distance(fruit_gt.translation().xy())
+                        <= range.0 as f32
+                });
+        if !in_range {
+            commands.entity(fruit_ent).remove::<HarvestReservation>();
         }
     }
 }
@@ -80,14 +488,19 @@ pub struct HarvesterPlugin;
 
 impl Plugin for HarvesterPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (
-                sys_harvester_look_for_fruit,
-                // sys_harvester_target_set,
-                // sys_harvester_move_to_target,
+        app.register_building::<HarvesterType>()
+            .register_building::<HarvesterWorkerType>()
+            .register_building::<DepotType>()
+            .add_systems(
+                Update,
+                (
+                    sys_release_harvest_reservations,
+                    sys_harvester_look_for_fruit,
+                    (sys_harvester_target_set, sys_harvester_move_to_target).chain(),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
             )
-                .run_if(in_state(GameState::Playing)),
-        );
+            .observe(obs_upgrade_harvester);
     }
 }
@@ -0,0 +1,383 @@
+use std::any::TypeId;
+
+use bevy::{ecs::system::SystemState, prelude::*};
+use bevy_ecs_tilemap::map::TilemapGridSize;
+
+use crate::{
+    input::{ActionInput, InputAction},
+    level::CurrentLevel,
+    placement::{Footprint, TileOccupancy},
+    ui::CurrentIntention,
+    Funds, PlayState,
+};
+
+use super::{
+    construction::{begin_construction, UnderConstruction},
+    demolish::Investment,
+    harvester::{Harvester, HarvesterWorker, TargetingPolicy},
+    upgrade::UpgradeTiers,
+    BuildingTypeId, BuildingTypeMap, TowerRange,
+};
+
+/// The parts of a building that player actions change after it is placed.
+/// Fields left as `None` are not touched when the state is applied.
+#[derive(Clone, Default)]
+pub struct BuildingState {
+    pub investment: Option<usize>,
+    pub tiers: Option<UpgradeTiers>,
+    pub policy: Option<TargetingPolicy>,
+    pub range: Option<TowerRange>,
+    pub harvester: Option<Harvester>,
+    pub worker: Option<HarvesterWorker>,
+    pub color: Option<Color>,
+}
+
+impl BuildingState {
+    pub fn capture(world: &World, entity: Entity) -> Self {
+        BuildingState {
+            investment: world.get::<Investment>(entity).map(|i| i.0),
+            tiers: world.get::<UpgradeTiers>(entity).cloned(),
+            policy: world.get::<TargetingPolicy>(entity).copied(),
+            range: world.get::<TowerRange>(entity).cloned(),
+            harvester: world.get::<Harvester>(entity).cloned(),
+            worker: world.get::<HarvesterWorker>(entity).cloned(),
+            color: world.get::<Sprite>(entity).map(|sprite| sprite.color),
+        }
+    }
+
+    pub fn apply(&self, world: &mut World, entity: Entity) {
+        let Some(mut building) = world.get_entity_mut(entity) else {
+            return;
+        };
+        if let Some(investment) = self.investment {
+            building.insert(Investment(investment));
+        }
+        if let Some(tiers) = self.tiers.clone() {
+            building.insert(tiers);
+        }
+        if let Some(policy) = self.policy {
+            building.insert(policy);
+        }
+        if let Some(range) = self.range.clone() {
+            building.insert(range);
+        }
+        if let Some(harvester) = self.harvester.clone() {
+            building.insert(harvester);
+        }
+        if let Some(worker) = self.worker.clone() {
+            building.insert(worker);
+        }
+        if let (Some(color), Some(mut sprite)) = (self.color, building.get_mut::<Sprite>()) {
+            sprite.color = color;
+        }
+    }
+}
+
+/// Everything needed to rebuild a removed building exactly as it was.
+#[derive(Clone)]
+pub struct BuildingSnapshot {
+    pub building_type: TypeId,
+    pub translation: Vec3,
+    pub footprint: Footprint,
+    pub construction: Option<Timer>,
+    pub state: BuildingState,
+}
+
+impl BuildingSnapshot {
+    pub fn capture(world: &World, entity: Entity) -> Option<Self> {
+        Some(BuildingSnapshot {
+            building_type: world.get::<BuildingTypeId>(entity)?.0,
+            translation: world.get::<Transform>(entity)?.translation,
+            footprint: world.get::<Footprint>(entity)?.clone(),
+            construction: world
+                .get::<UnderConstruction>(entity)
+                .map(|construction| construction.timer.clone()),
+            state: BuildingState::capture(world, entity),
+        })
+    }
+
+    fn tiles_free(&self, world: &World) -> bool {
+        let occupancy = world.resource::<TileOccupancy>();
+        !self
+            .footprint
+            .tiles
+            .iter()
+            .any(|tile_pos| occupancy.0.contains_key(tile_pos))
+    }
+
+    /// Spawns the building again and returns its new entity.
+    fn restore(&self, world: &mut World) -> Option<Entity> {
+        let grid_size = *world
+            .query_filtered::<&TilemapGridSize, With<CurrentLevel>>()
+            .get_single(world)
+            .ok()?;
+        let size = self.footprint.size.as_vec2() * Vec2::new(grid_size.x, grid_size.y);
+        let entity = world
+            .spawn(SpatialBundle {
+                transform: Transform::from_translation(self.translation),
+                ..Default::default()
+            })
+            .id();
+        world.resource_scope(|world, building_types: Mut<BuildingTypeMap>| {
+            let building_type = building_types.type_map.get(&self.building_type)?;
+            let mut commands = world.commands();
+            match self.construction {
+                Some(_) => begin_construction(
+                    &mut commands,
+                    entity,
+                    self.building_type,
+                    building_type.as_ref(),
+                    size,
+                ),
+                None => building_type.construct_building(&mut commands, entity),
+            }
+            Some(())
+        })?;
+        world.flush();
+        if let (Some(timer), Some(mut construction)) = (
+            self.construction.clone(),
+            world.get_mut::<UnderConstruction>(entity),
+        ) {
+            construction.timer = timer;
+        }
+        self.state.apply(world, entity);
+        world
+            .resource_mut::<TileOccupancy>()
+            .occupy(&self.footprint, entity);
+        world
+            .entity_mut(entity)
+            .insert((self.footprint.clone(), BuildingTypeId(self.building_type)));
+        Some(entity)
+    }
+}
+
+pub enum BuildAction {
+    Place {
+        entity: Entity,
+        snapshot: BuildingSnapshot,
+    },
+    Demolish {
+        entity: Entity,
+        snapshot: BuildingSnapshot,
+        refund: usize,
+    },
+    /// An upgrade or targeting change. `cost` is what it took from funds.
+    Modify {
+        entity: Entity,
+        before: BuildingState,
+        after: BuildingState,
+        cost: usize,
+    },
+}
+
+impl BuildAction {
+    fn entity_mut(&mut self) -> &mut Entity {
+        match self {
+            BuildAction::Place { entity, .. }
+            | BuildAction::Demolish { entity, .. }
+            | BuildAction::Modify { entity, .. } => entity,
+        }
+    }
+}
+
+/// Building actions taken during the current intermission.
+#[derive(Resource, Default)]
+pub struct BuildHistory {
+    undo: Vec<BuildAction>,
+    redo: Vec<BuildAction>,
+}
+
+impl BuildHistory {
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Points actions at a building that was respawned by undo or redo.
+    fn remap(&mut self, old: Entity, new: Entity) {
+        for action in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            let entity = action.entity_mut();
+            if *entity == old {
+                *entity = new;
+            }
+        }
+    }
+}
+
+/// Adds an action to the history if it happened during an intermission.
+/// Taking a new action discards anything that could be redone.
+pub fn record_action(world: &mut World, action: BuildAction) {
+    let in_intermission = world
+        .get_resource::<State<PlayState>>()
+        .is_some_and(|state| *state.get() == PlayState::Intermission);
+    if !in_intermission {
+        return;
+    }
+    let mut history = world.resource_mut::<BuildHistory>();
+    history.redo.clear();
+    history.undo.push(action);
+}
+
+fn remove_building(world: &mut World, entity: Entity, footprint: &Footprint, refund: usize) {
+    world.resource_mut::<TileOccupancy>().release(footprint);
+    world.resource_mut::<Funds>().0 += refund;
+    if let Some(building) = world.get_entity_mut(entity) {
+        building.despawn_recursive();
+    }
+    let selected = match *world.resource::<CurrentIntention>() {
+        CurrentIntention::Inspect(_, ent) | CurrentIntention::Command(_, ent) => ent == entity,
+        _ => false,
+    };
+    if selected {
+        world.insert_resource(CurrentIntention::None);
+    }
+}
+
+fn rebuild(world: &mut World, snapshot: &BuildingSnapshot, price: usize) -> Option<Entity> {
+    if world.resource::<Funds>().0 < price {
+        info!("Can't afford to restore the building");
+        return None;
+    }
+    if !snapshot.tiles_free(world) {
+        info!("Can't restore the building, its tiles are occupied");
+        return None;
+    }
+    let entity = snapshot.restore(world)?;
+    world.resource_mut::<Funds>().0 -= price;
+    Some(entity)
+}
+
+/// Reverts `action`, returning whether that succeeded.
+fn undo_action(world: &mut World, action: &mut BuildAction) -> bool {
+    match action {
+        BuildAction::Place { entity, snapshot } => {
+            let refund = snapshot.state.investment.unwrap_or_default();
+            remove_building(world, *entity, &snapshot.footprint, refund);
+            true
+        }
+        BuildAction::Demolish {
+            entity,
+            snapshot,
+            refund,
+        } => {
+            let Some(new_entity) = rebuild(world, snapshot, *refund) else {
+                return false;
+            };
+            world
+                .resource_mut::<BuildHistory>()
+                .remap(*entity, new_entity);
+            *entity = new_entity;
+            true
+        }
+        BuildAction::Modify {
+            entity,
+            before,
+            cost,
+            ..
+        } => {
+            before.apply(world, *entity);
+            world.resource_mut::<Funds>().0 += *cost;
+            true
+        }
+    }
+}
+
+/// Applies `action` again, returning whether that succeeded.
+fn redo_action(world: &mut World, action: &mut BuildAction) -> bool {
+    match action {
+        BuildAction::Place { entity, snapshot } => {
+            let price = snapshot.state.investment.unwrap_or_default();
+            let Some(new_entity) = rebuild(world, snapshot, price) else {
+                return false;
+            };
+            world
+                .resource_mut::<BuildHistory>()
+                .remap(*entity, new_entity);
+            *entity = new_entity;
+            true
+        }
+        BuildAction::Demolish {
+            entity,
+            snapshot,
+            refund,
+        } => {
+            remove_building(world, *entity, &snapshot.footprint, *refund);
+            true
+        }
+        BuildAction::Modify {
+            entity,
+            after,
+            cost,
+            ..
+        } => {
+            if world.resource::<Funds>().0 < *cost {
+                info!("Can't afford to redo the upgrade");
+                return false;
+            }
+            after.apply(world, *entity);
+            world.resource_mut::<Funds>().0 -= *cost;
+            true
+        }
+    }
+}
+
+pub fn undo(world: &mut World) {
+    let Some(mut action) = world.resource_mut::<BuildHistory>().undo.pop() else {
+        return;
+    };
+    let undone = undo_action(world, &mut action);
+    let mut history = world.resource_mut::<BuildHistory>();
+    if undone {
+        history.redo.push(action);
+    } else {
+        history.undo.push(action);
+    }
+}
+
+pub fn redo(world: &mut World) {
+    let Some(mut action) = world.resource_mut::<BuildHistory>().redo.pop() else {
+        return;
+    };
+    let redone = redo_action(world, &mut action);
+    let mut history = world.resource_mut::<BuildHistory>();
+    if redone {
+        history.undo.push(action);
+    } else {
+        history.redo.push(action);
+    }
+}
+
+pub fn sys_undo_redo_hotkeys(world: &mut World, input: &mut SystemState<ActionInput>) {
+    let input = input.get(world);
+    let (undo_pressed, redo_pressed) = (
+        input.just_pressed(InputAction::Undo),
+        input.just_pressed(InputAction::Redo),
+    );
+    if redo_pressed {
+        redo(world);
+    } else if undo_pressed {
+        undo(world);
+    }
+}
+
+pub fn sys_clear_build_history(mut history: ResMut<BuildHistory>) {
+    history.undo.clear();
+    history.redo.clear();
+}
+
+pub struct BuildHistoryPlugin;
+
+impl Plugin for BuildHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BuildHistory>()
+            .add_systems(
+                Update,
+                sys_undo_redo_hotkeys.run_if(in_state(PlayState::Intermission)),
+            )
+            .add_systems(OnEnter(PlayState::Wave), sys_clear_build_history)
+            .add_systems(OnEnter(PlayState::Setup), sys_clear_build_history);
+    }
+}
@@ -0,0 +1,104 @@
+use std::{any::TypeId, collections::HashMap};
+
+use bevy::prelude::*;
+use bevy_mod_picking::{events::Pointer, prelude::On, selection::Select, PickableBundle};
+use bevy_rapier2d::prelude::{ActiveEvents, Collider, CollisionEvent, Sensor};
+
+use crate::{
+    fruit::{collect_fallen_fruit, FallenFruit},
+    ui::CurrentIntention,
+    units::Building,
+    GameState,
+};
+
+use super::{definition::BuildingStats, harvester::HarvestStats, BuildingAppExt};
+
+/// Catches ripe fallen fruit that rolls into its sensor collider.
+#[derive(Component)]
+pub struct Net;
+
+#[derive(Default)]
+pub struct NetType {
+    stats: BuildingStats,
+    half_extent: f32,
+}
+
+impl Building for NetType {
+    fn kind(&self) -> &'static str {
+        "net"
+    }
+
+    fn stats(&self) -> &BuildingStats {
+        &self.stats
+    }
+
+    fn stats_mut(&mut self) -> &mut BuildingStats {
+        &mut self.stats
+    }
+
+    fn params(&self) -> &'static [&'static str] {
+        &["half_extent"]
+    }
+
+    fn configure(&mut self, params: &HashMap<String, f32>) {
+        if let Some(half_extent) = params.get("half_extent") {
+            self.half_extent = *half_extent;
+        }
+    }
+
+    fn construct_building(&self, commands: &mut Commands, target: Entity) {
+        commands.entity(target).insert((
+            Net,
+            HarvestStats::default(),
+            Collider::cuboid(self.half_extent, self.half_extent),
+            Sensor,
+            ActiveEvents::COLLISION_EVENTS,
+            self.stats.sprite_handle.clone(),
+            Sprite {
+                ..Default::default()
+            },
+            PickableBundle::default(),
+            On::<Pointer<Select>>::commands_mut(|event, commands| {
+                commands.insert_resource(CurrentIntention::Command(
+                    TypeId::of::<NetType>(),
+                    event.target,
+                ));
+            }),
+        ));
+    }
+}
+
+pub fn sys_net_collect_fruit(
+    mut commands: Commands,
+    mut collisions: EventReader<CollisionEvent>,
+    nets: Query<(), With<Net>>,
+    fallen: Query<(&FallenFruit, &GlobalTransform)>,
+) {
+    for collision in collisions.read() {
+        let CollisionEvent::Started(a, b, _) = *collision else {
+            continue;
+        };
+        let (net_ent, fruit_ent) = if nets.contains(a) { (a, b) } else { (b, a) };
+        if !nets.contains(net_ent) {
+            continue;
+        }
+        let Ok((fallen, fruit_gt)) = fallen.get(fruit_ent) else {
+            continue;
+        };
+        if !fallen.ripe {
+            continue;
+        }
+        collect_fallen_fruit(&mut commands, fruit_ent, fruit_gt.translation(), net_ent);
+    }
+}
+
+pub struct NetPlugin;
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_building::<NetType>().add_systems(
+            Update,
+            sys_net_collect_fruit.run_if(in_state(GameState::Playing)),
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::{any::TypeId, collections::HashMap};
+
+use bevy::prelude::*;
+use bevy_mod_picking::{events::Pointer, prelude::On, selection::Select, PickableBundle};
+
+use crate::{
+    fruit::{
+        FruitBranchAttachment, FruitGrowthState, FruitKnockedOffEvent, HarvestFruitEvent,
+        HarvestReservation,
+    },
+    tree::Tree,
+    ui::CurrentIntention,
+    units::Building,
+    GameState,
+};
+
+use super::{definition::BuildingStats, harvester::HarvestStats, BuildingAppExt, TowerRange};
+
+/// Area-of-effect harvester that periodically shakes every tree in range.
+/// Ripe fruit is collected; unripe fruit is knocked off and lost.
+#[derive(Component)]
+pub struct Shaker {
+    pub timer: Timer,
+}
+
+#[derive(Default)]
+pub struct ShakerType {
+    stats: BuildingStats,
+    period_secs: f32,
+}
+
+impl Building for ShakerType {
+    fn kind(&self) -> &'static str {
+        "shaker"
+    }
+
+    fn stats(&self) -> &BuildingStats {
+        &self.stats
+    }
+
+    fn stats_mut(&mut self) -> &mut BuildingStats {
+        &mut self.stats
+    }
+
+    fn params(&self) -> &'static [&'static str] {
+        &["period_secs"]
+    }
+
+    fn configure(&mut self, params: &HashMap<String, f32>) {
+        if let Some(period_secs) = params.get("period_secs") {
+            self.period_secs = *period_secs;
+        }
+    }
+
+    fn construct_building(&self, commands: &mut Commands, target: Entity) {
+        commands.entity(target).insert((
+            Shaker {
+                timer: Timer::from_seconds(self.period_secs, TimerMode::Repeating),
+            },
+            TowerRange(self.stats.range.unwrap_or_default()),
+            HarvestStats::default(),
+            self.stats.sprite_handle.clone(),
+            Sprite {
+                ..Default::default()
+            },
+            PickableBundle::default(),
+            On::<Pointer<Select>>::commands_mut(|event, commands| {
+                commands.insert_resource(CurrentIntention::Command(
+                    TypeId::of::<ShakerType>(),
+                    event.target,
+                ));
+            }),
+        ));
+    }
+}
+
+pub fn sys_shaker_shake_trees(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut shakers: Query<(Entity, &mut Shaker, &GlobalTransform, &TowerRange)>,
+    trees: Query<(&GlobalTransform, &Children), With<Tree>>,
+    branches: Query<&FruitBranchAttachment>,
+    fruit: Query<&FruitGrowthState>,
+) {
+    for (shaker_ent, mut shaker, transform, range) in shakers.iter_mut() {
+        shaker.timer.tick(time.delta());
+        if !shaker.timer.just_finished() {
+            continue;
+        }
+        let origin = transform.translation().xy();
+        let shaken_fruit = trees
+            .iter()
+            .filter(|(tree_gt, _)| tree_gt.translation().xy().distance(origin) <= range.0 as f32)
+            .flat_map(|(_, children)| children.iter())
+            .filter_map(|branch_ent| branches.get(*branch_ent).ok())
+            .map(|FruitBranchAttachment::Fruit(fruit_ent)| *fruit_ent);
+        for fruit_ent in shaken_fruit {
+            match fruit.get(fruit_ent) {
+                Ok(FruitGrowthState::Fruited { .. }) => {
+                    // Shaking beats any other harvester's claim on the fruit.
+                    commands.entity(fruit_ent).insert(HarvestReservation {
+                        harvester_ent: shaker_ent,
+                    });
+                    commands.trigger_targets(
+                        HarvestFruitEvent {
+                            harvester_ent: shaker_ent,
+                        },
+                        fruit_ent,
+                    );
+                }
+                Ok(FruitGrowthState::Bud { .. }) => {
+                    commands.trigger_targets(FruitKnockedOffEvent, fruit_ent);
+                }
+                Err(_) => (),
+            }
+        }
+    }
+}
+
+pub struct ShakerPlugin;
+
+impl Plugin for ShakerPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_building::<ShakerType>().add_systems(
+            Update,
+            sys_shaker_shake_trees.run_if(in_state(GameState::Playing)),
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use bevy::prelude::*;
+
+use crate::Funds;
+
+use super::{
+    demolish::Investment,
+    harvester::{Harvester, HarvesterWorker},
+    history::{record_action, BuildAction, BuildingState},
+    TowerRange,
+};
+
+pub const MAX_UPGRADE_TIER: u32 = 3;
+
+/// Cost of buying each tier, indexed by the tier currently owned.
+pub const UPGRADE_TIER_COSTS: [usize; MAX_UPGRADE_TIER as usize] = [10, 25, 50];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpgradeTrack {
+    Range,
+    HarvestSpeed,
+    Capacity,
+    TargetCount,
+}
+
+impl UpgradeTrack {
+    pub const ALL: [UpgradeTrack; 4] = [
+        UpgradeTrack::Range,
+        UpgradeTrack::HarvestSpeed,
+        UpgradeTrack::Capacity,
+        UpgradeTrack::TargetCount,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            UpgradeTrack::Range => "Range",
+            UpgradeTrack::HarvestSpeed => "Harvest speed",
+            UpgradeTrack::Capacity => "Capacity",
+            UpgradeTrack::TargetCount => "Targets per pick",
+        }
+    }
+
+    /// Whether the track does anything for a harvester with the given parts.
+    pub fn applies_to(
+        &self,
+        harvester: Option<&Harvester>,
+        worker: Option<&HarvesterWorker>,
+    ) -> bool {
+        match self {
+            UpgradeTrack::Range | UpgradeTrack::HarvestSpeed => {
+                harvester.is_some() || worker.is_some()
+            }
+            UpgradeTrack::Capacity => worker.is_some(),
+            UpgradeTrack::TargetCount => harvester.is_some(),
+        }
+    }
+}
+
+/// Tier bought in each upgrade track.
+#[derive(Component, Default, Clone)]
+pub struct UpgradeTiers {
+    pub range: u32,
+    pub harvest_speed: u32,
+    pub capacity: u32,
+    pub target_count: u32,
+}
+
+impl UpgradeTiers {
+    pub fn tier(&self, track: UpgradeTrack) -> u32 {
+        match track {
+            UpgradeTrack::Range => self.range,
+            UpgradeTrack::HarvestSpeed => self.harvest_speed,
+            UpgradeTrack::Capacity => self.capacity,
+            UpgradeTrack::TargetCount => self.target_count,
+        }
+    }
+
+    fn tier_mut(&mut self, track: UpgradeTrack) -> &mut u32 {
+        match track {
+            UpgradeTrack::Range => &mut self.range,
+            UpgradeTrack::HarvestSpeed => &mut self.harvest_speed,
+            UpgradeTrack::Capacity => &mut self.capacity,
+            UpgradeTrack::TargetCount => &mut self.target_count,
+        }
+    }
+
+    /// Cost of the next tier in `track`, or `None` once it is maxed out.
+    pub fn next_cost(&self, track: UpgradeTrack) -> Option<usize> {
+        UPGRADE_TIER_COSTS.get(self.tier(track) as usize).copied()
+    }
+
+    pub fn total(&self) -> u32 {
+        self.range + self.harvest_speed + self.capacity + self.target_count
+    }
+
+    /// Colour used for the sprite tint and range circle of an upgraded building.
+    pub fn tier_color(&self) -> Color {
+        let progress = self.total() as f32 / (MAX_UPGRADE_TIER * 4) as f32;
+        Color::WHITE.mix(&Color::srgb(1.0, 0.8, 0.2), progress.min(1.0))
+    }
+}
+
+#[derive(Event)]
+pub struct UpgradeHarvesterEvent {
+    pub track: UpgradeTrack,
+}
+
+pub fn obs_upgrade_harvester(
+    event: Trigger<UpgradeHarvesterEvent>,
+    mut commands: Commands,
+    mut funds: ResMut<Funds>,
+    mut harvesters: Query<(
+        &mut UpgradeTiers,
+        &mut Sprite,
+        Option<&mut TowerRange>,
+        Option<&mut Harvester>,
+        Option<&mut HarvesterWorker>,
+        Option<&mut Investment>,
+    )>,
+) {
+    let track = event.event().track;
+    let Ok((mut tiers, mut sprite, range, harvester, worker, investment)) =
+        harvesters.get_mut(event.entity())
+    else {
+        return;
+    };
+    if !track.applies_to(harvester.as_deref(), worker.as_deref()) {
+        return;
+    }
+    let Some(cost) = tiers.next_cost(track) else {
+        return;
+    };
+    if funds.0 < cost {
+        return;
+    }
+
+    let before = BuildingState {
+        investment: investment.as_deref().map(|investment| investment.0),
+        tiers: Some(tiers.clone()),
+        policy: None,
+        range: range.as_deref().cloned(),
+        harvester: harvester.as_deref().cloned(),
+        worker: worker.as_deref().cloned(),
+        color: Some(sprite.color),
+    };
+    funds.0 -= cost;
+    if let Some(mut investment) = investment {
+        investment.0 += cost;
+    }
+    *tiers.tier_mut(track) += 1;
+    match track {
+        UpgradeTrack::Range => {
+            if let Some(mut range) = range {
+                range.0 += 15;
+            }
+        }
+        UpgradeTrack::HarvestSpeed => {
+            if let Some(mut harvester) = harvester {
+                let cooldown_secs = harvester.cooldown_secs * 0.75;
+                harvester.set_cooldown_secs(cooldown_secs);
+            }
+            if let Some(mut worker) = worker {
+                worker.speed *= 1.25;
+            }
+        }
+        UpgradeTrack::Capacity => {
+            if let Some(mut worker) = worker {
+                worker.capacity += 2;
+            }
+        }
+        UpgradeTrack::TargetCount => {
+            if let Some(mut harvester) = harvester {
+                harvester.targets_per_pick += 1;
+            }
+        }
+    }
+    sprite.color = tiers.tier_color();
+
+    let entity = event.entity();
+    commands.add(move |world: &mut World| {
+        let after = BuildingState::capture(world, entity);
+        record_action(
+            world,
+            BuildAction::Modify {
+                entity,
+                before,
+                after,
+                cost,
+            },
+        );
+    });
+}
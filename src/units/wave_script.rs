@@ -0,0 +1,124 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::escape::EscapeRules;
+
+use crate::fruit_type::FruitGenus;
+
+/// A batch of new wild trees sent along the path during a wave.
+#[derive(Deserialize, Clone, Debug)]
+pub struct TreeGroup {
+    /// Species of every tree in the group. Without one, the group cycles
+    /// through all species.
+    #[serde(default)]
+    pub species: Option<FruitGenus>,
+    pub count: usize,
+    /// Seconds between trees of the group entering the path.
+    pub spacing_secs: f32,
+    /// Path tiles walked per second.
+    pub speed: f32,
+    /// Seconds after the wave starts before the group's first tree.
+    #[serde(default)]
+    pub delay_secs: f32,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct WaveDefinition {
+    #[serde(default)]
+    pub groups: Vec<TreeGroup>,
+    /// Spacing for the trees the player queued during the intermission.
+    #[serde(default = "default_queued_spacing_secs")]
+    pub queued_spacing_secs: f32,
+    #[serde(default = "default_queued_speed")]
+    pub queued_speed: f32,
+}
+
+fn default_queued_spacing_secs() -> f32 {
+    1.0
+}
+
+fn default_queued_speed() -> f32 {
+    20.0
+}
+
+impl Default for WaveDefinition {
+    /// Used while no wave script is loaded.
+    fn default() -> Self {
+        WaveDefinition {
+            groups: vec![TreeGroup {
+                species: None,
+                count: 10,
+                spacing_secs: 1.0,
+                speed: 20.0,
+                delay_secs: 0.0,
+            }],
+            queued_spacing_secs: default_queued_spacing_secs(),
+            queued_speed: default_queued_speed(),
+        }
+    }
+}
+
+/// The waves of one level, in order.
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct WaveScript {
+    #[serde(default)]
+    pub escape: EscapeRules,
+    pub waves: Vec<WaveDefinition>,
+}
+
+impl WaveScript {
+    /// Definition of wave `index`. Once the script runs out, its last wave
+    /// repeats.
+    pub fn wave(&self, index: usize) -> Option<&WaveDefinition> {
+        self.waves.get(index).or(self.waves.last())
+    }
+}
+
+#[derive(Default)]
+pub struct WaveScriptLoader;
+
+#[derive(Error, Debug)]
+pub enum WaveScriptLoaderError {
+    #[error("Could not read the wave script: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse the wave script: {0}")]
+    Ron(#[from] ron::de::SpannedError),
+}
+
+impl AssetLoader for WaveScriptLoader {
+    type Asset = WaveScript;
+    type Settings = ();
+    type Error = WaveScriptLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["waves.ron"]
+    }
+}
+
+/// Wave script of the level being played.
+#[derive(Resource)]
+pub struct WaveScriptHandle(pub Handle<WaveScript>);
+
+pub struct WaveScriptPlugin;
+
+impl Plugin for WaveScriptPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<WaveScript>()
+            .init_asset_loader::<WaveScriptLoader>();
+    }
+}
@@ -9,7 +9,6 @@ use bevy::{
         system::{Commands, Query, Res, ResMut},
     },
     gizmos::gizmos,
-    input::{keyboard::KeyCode, ButtonInput},
     math::{Quat, Vec2, Vec3Swizzles},
     prelude::{default, SpatialBundle},
     render::view::{InheritedVisibility, Visibility},
@@ -25,7 +24,10 @@ use bevy_rapier2d::{
     geometry::Collider,
 };
 
-use crate::GameState;
+use crate::{
+    input::{ActionInput, InputAction},
+    GameState,
+};
 
 pub struct VotingPlugin;
 
@@ -103,19 +105,19 @@ fn sys_init_level(mut next_play_state: ResMut<NextState<GameState>>, mut command
 fn sys_move_draw_player(
     mut player: Query<(&mut Transform, &InheritedVisibility), With<Player>>,
     mut gizmos: gizmos::Gizmos,
-    keys: Res<ButtonInput<KeyCode>>,
+    input: ActionInput,
 ) {
     let (mut transform, visibility) = player.single_mut();
-    if keys.pressed(KeyCode::KeyW) {
+    if input.pressed(InputAction::MoveUp) {
         transform.translation.y += 1.;
     }
-    if keys.pressed(KeyCode::KeyS) {
+    if input.pressed(InputAction::MoveDown) {
         transform.translation.y -= 1.;
     }
-    if keys.pressed(KeyCode::KeyD) {
+    if input.pressed(InputAction::MoveRight) {
         transform.translation.x += 1.;
     }
-    if keys.pressed(KeyCode::KeyA) {
+    if input.pressed(InputAction::MoveLeft) {
         transform.translation.x -= 1.;
     }
 